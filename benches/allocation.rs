@@ -0,0 +1,36 @@
+//! Compares the global `fetch_add` path with per-thread blocks as the number of threads grows.
+//!
+//! Run with `cargo bench --features std`.
+
+use runtime_id::RuntimeID;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const IDS_PER_THREAD: usize = 1_000_000;
+
+fn run(threads: usize, create: fn() -> RuntimeID) -> Duration {
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for _ in 0..IDS_PER_THREAD {
+                    black_box(create());
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    for threads in [1, 2, 4, 8] {
+        let global = run(threads, RuntimeID::new);
+        let local = run(threads, RuntimeID::new_local);
+        let per_id = |elapsed: Duration| elapsed.as_nanos() as f64 / (threads * IDS_PER_THREAD) as f64;
+        println!(
+            "{threads} thread(s): new {:.2} ns/id, new_local {:.2} ns/id",
+            per_id(global),
+            per_id(local)
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Compares building and querying a map keyed by `RuntimeID` with different hashers.
+//!
+//! Run with `cargo bench --features std`.
+
+use runtime_id::{BuildRuntimeIdHasher, RuntimeID};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::hint::black_box;
+use std::time::Instant;
+
+const IDS: usize = 1_000_000;
+
+fn run<S: BuildHasher + Default>(name: &str, ids: &[RuntimeID]) {
+    let start = Instant::now();
+    let mut map = HashMap::with_capacity_and_hasher(ids.len(), S::default());
+    for (index, &id) in ids.iter().enumerate() {
+        map.insert(id, index);
+    }
+    let inserted = start.elapsed();
+
+    let start = Instant::now();
+    for id in ids {
+        black_box(map.get(id));
+    }
+    let looked_up = start.elapsed();
+
+    println!(
+        "{name:>12}: insert {:.2} ns/id, lookup {:.2} ns/id",
+        inserted.as_nanos() as f64 / ids.len() as f64,
+        looked_up.as_nanos() as f64 / ids.len() as f64
+    );
+}
+
+fn main() {
+    let ids: Vec<RuntimeID> = RuntimeID::reserve(IDS).collect();
+    run::<RandomState>("SipHash", &ids);
+    run::<ahash::RandomState>("AHash", &ids);
+    run::<BuildRuntimeIdHasher>("RuntimeID", &ids);
+}
@@ -0,0 +1,93 @@
+//! A 64-bit atomic that works on every target the crate supports.
+//!
+//! Targets with native 64-bit atomics use `AtomicU64` directly. Elsewhere, or when the `atomic64-fallback` feature is
+//! enabled to exercise this path on desktop targets, the value is guarded by a spinlock built from an `AtomicBool`.
+
+#[cfg(all(target_has_atomic = "64", not(feature = "atomic64-fallback")))]
+mod imp {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    pub(crate) struct Atomic64(AtomicU64);
+
+    impl Atomic64 {
+        pub(crate) const fn new(value: u64) -> Self {
+            Atomic64(AtomicU64::new(value))
+        }
+
+        #[inline]
+        pub(crate) fn store(&self, value: u64) {
+            self.0.store(value, Ordering::Relaxed)
+        }
+
+        #[inline]
+        pub(crate) fn fetch_add(&self, value: u64) -> u64 {
+            self.0.fetch_add(value, Ordering::Relaxed)
+        }
+
+        #[inline]
+        pub(crate) fn fetch_update(&self, f: impl FnMut(u64) -> Option<u64>) -> Result<u64, u64> {
+            self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, f)
+        }
+    }
+}
+
+#[cfg(any(not(target_has_atomic = "64"), feature = "atomic64-fallback"))]
+mod imp {
+    use core::cell::UnsafeCell;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    pub(crate) struct Atomic64 {
+        locked: AtomicBool,
+        value: UnsafeCell<u64>,
+    }
+
+    // SAFETY: `value` is only accessed while `locked` is held.
+    unsafe impl Sync for Atomic64 {}
+
+    impl Atomic64 {
+        pub(crate) const fn new(value: u64) -> Self {
+            Atomic64 { locked: AtomicBool::new(false), value: UnsafeCell::new(value) }
+        }
+
+        #[inline]
+        fn with<R>(&self, f: impl FnOnce(&mut u64) -> R) -> R {
+            while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+                core::hint::spin_loop();
+            }
+            // SAFETY: the lock is held until the store below, and none of the closures passed in can panic.
+            let result = f(unsafe { &mut *self.value.get() });
+            self.locked.store(false, Ordering::Release);
+            result
+        }
+
+        #[inline]
+        pub(crate) fn store(&self, new: u64) {
+            self.with(|value| *value = new)
+        }
+
+        #[inline]
+        pub(crate) fn fetch_add(&self, add: u64) -> u64 {
+            self.with(|value| {
+                let old = *value;
+                *value = old.wrapping_add(add);
+                old
+            })
+        }
+
+        #[inline]
+        pub(crate) fn fetch_update(&self, mut f: impl FnMut(u64) -> Option<u64>) -> Result<u64, u64> {
+            self.with(|value| {
+                let old = *value;
+                match f(old) {
+                    Some(new) => {
+                        *value = new;
+                        Ok(old)
+                    }
+                    None => Err(old),
+                }
+            })
+        }
+    }
+}
+
+pub(crate) use imp::Atomic64;
@@ -0,0 +1,353 @@
+use crate::TaggedRuntimeID;
+use core::fmt;
+use core::marker::PhantomData;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[inline]
+fn to_id<Tag>(raw: usize) -> TaggedRuntimeID<Tag> {
+    match NonZeroUsize::new(raw) {
+        Some(id) => TaggedRuntimeID::from_raw(id),
+        None => unreachable!("an AtomicRuntimeID always holds an ID"),
+    }
+}
+
+#[inline]
+fn to_option<Tag>(raw: usize) -> Option<TaggedRuntimeID<Tag>> {
+    NonZeroUsize::new(raw).map(TaggedRuntimeID::from_raw)
+}
+
+#[inline]
+fn from_option<Tag>(id: Option<TaggedRuntimeID<Tag>>) -> usize {
+    id.map_or(0, |id| id.id.get())
+}
+
+/// A [`TaggedRuntimeID`] that can be shared and replaced between threads without a lock. The tag defaults to
+/// `()`, holding a [`RuntimeID`](crate::RuntimeID).
+///
+/// This is an [`AtomicUsize`] holding the ID's value, and every method behaves like the method of the same name on
+/// [`AtomicUsize`], taking the same [`Ordering`]s.
+///
+/// # Example
+/// ```
+/// use runtime_id::{AtomicRuntimeID, RuntimeID};
+/// use std::sync::atomic::Ordering;
+///
+/// let first = RuntimeID::new();
+/// let owner = AtomicRuntimeID::new(first);
+///
+/// let second = RuntimeID::new();
+/// assert_eq!(owner.compare_exchange(first, second, Ordering::AcqRel, Ordering::Acquire), Ok(first));
+/// assert_eq!(owner.load(Ordering::Acquire), second);
+/// ```
+#[repr(transparent)]
+pub struct AtomicRuntimeID<Tag = ()> {
+    id: AtomicUsize,
+    tag: PhantomData<fn() -> Tag>,
+}
+
+impl<Tag> AtomicRuntimeID<Tag> {
+    /// Creates a cell holding `id`.
+    #[inline]
+    pub const fn new(id: TaggedRuntimeID<Tag>) -> Self {
+        AtomicRuntimeID { id: AtomicUsize::new(id.id.get()), tag: PhantomData }
+    }
+
+    /// Consumes the cell and returns the ID it holds.
+    #[inline]
+    pub fn into_inner(self) -> TaggedRuntimeID<Tag> {
+        to_id(self.id.into_inner())
+    }
+
+    /// Returns the ID held. See [`AtomicUsize::load`].
+    #[inline]
+    pub fn load(&self, order: Ordering) -> TaggedRuntimeID<Tag> {
+        to_id(self.id.load(order))
+    }
+
+    /// Replaces the ID held with `id`. See [`AtomicUsize::store`].
+    #[inline]
+    pub fn store(&self, id: TaggedRuntimeID<Tag>, order: Ordering) {
+        self.id.store(id.id.get(), order);
+    }
+
+    /// Replaces the ID held with `id` and returns the previous one. See [`AtomicUsize::swap`].
+    #[inline]
+    pub fn swap(&self, id: TaggedRuntimeID<Tag>, order: Ordering) -> TaggedRuntimeID<Tag> {
+        to_id(self.id.swap(id.id.get(), order))
+    }
+
+    /// Replaces the ID held with `new` if it is `current`. Returns the previous ID, as `Ok` if it was replaced. See
+    /// [`AtomicUsize::compare_exchange`].
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: TaggedRuntimeID<Tag>,
+        new: TaggedRuntimeID<Tag>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<TaggedRuntimeID<Tag>, TaggedRuntimeID<Tag>> {
+        self.id.compare_exchange(current.id.get(), new.id.get(), success, failure).map(to_id).map_err(to_id)
+    }
+
+    /// Like [`compare_exchange`](Self::compare_exchange), but may fail spuriously. See
+    /// [`AtomicUsize::compare_exchange_weak`].
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: TaggedRuntimeID<Tag>,
+        new: TaggedRuntimeID<Tag>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<TaggedRuntimeID<Tag>, TaggedRuntimeID<Tag>> {
+        self.id.compare_exchange_weak(current.id.get(), new.id.get(), success, failure).map(to_id).map_err(to_id)
+    }
+
+    /// Replaces the ID held with the one `f` returns for it, retrying if another thread changed it in between.
+    /// Returns the previous ID, as `Err` if `f` returned `None`. See [`AtomicUsize::fetch_update`].
+    #[inline]
+    pub fn fetch_update(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: impl FnMut(TaggedRuntimeID<Tag>) -> Option<TaggedRuntimeID<Tag>>,
+    ) -> Result<TaggedRuntimeID<Tag>, TaggedRuntimeID<Tag>> {
+        self.id
+            .fetch_update(set_order, fetch_order, |raw| f(to_id(raw)).map(|id| id.id.get()))
+            .map(to_id)
+            .map_err(to_id)
+    }
+}
+
+impl<Tag> From<TaggedRuntimeID<Tag>> for AtomicRuntimeID<Tag> {
+    #[inline]
+    fn from(id: TaggedRuntimeID<Tag>) -> Self {
+        Self::new(id)
+    }
+}
+
+impl<Tag> fmt::Debug for AtomicRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+/// An `Option<TaggedRuntimeID<Tag>>` that can be shared and replaced between threads without a lock. The tag
+/// defaults to `()`, holding an `Option<RuntimeID>`.
+///
+/// This is an [`AtomicUsize`] holding the ID's value, or zero for `None`, which no ID ever has. Every method behaves
+/// like the method of the same name on [`AtomicUsize`], taking the same [`Ordering`]s.
+///
+/// # Example
+/// ```
+/// use runtime_id::{AtomicOptionRuntimeID, RuntimeID};
+/// use std::sync::atomic::Ordering;
+///
+/// let owner = AtomicOptionRuntimeID::new(None);
+///
+/// let id = RuntimeID::new();
+/// assert_eq!(owner.compare_exchange(None, Some(id), Ordering::AcqRel, Ordering::Acquire), Ok(None));
+/// assert_eq!(owner.swap(None, Ordering::AcqRel), Some(id));
+/// ```
+#[repr(transparent)]
+pub struct AtomicOptionRuntimeID<Tag = ()> {
+    id: AtomicUsize,
+    tag: PhantomData<fn() -> Tag>,
+}
+
+impl<Tag> AtomicOptionRuntimeID<Tag> {
+    /// Creates a cell holding `id`.
+    #[inline]
+    pub const fn new(id: Option<TaggedRuntimeID<Tag>>) -> Self {
+        let raw = match id {
+            Some(id) => id.id.get(),
+            None => 0,
+        };
+        AtomicOptionRuntimeID { id: AtomicUsize::new(raw), tag: PhantomData }
+    }
+
+    /// Consumes the cell and returns the ID it holds.
+    #[inline]
+    pub fn into_inner(self) -> Option<TaggedRuntimeID<Tag>> {
+        to_option(self.id.into_inner())
+    }
+
+    /// Returns the ID held. See [`AtomicUsize::load`].
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<TaggedRuntimeID<Tag>> {
+        to_option(self.id.load(order))
+    }
+
+    /// Replaces the ID held with `id`. See [`AtomicUsize::store`].
+    #[inline]
+    pub fn store(&self, id: Option<TaggedRuntimeID<Tag>>, order: Ordering) {
+        self.id.store(from_option(id), order);
+    }
+
+    /// Replaces the ID held with `id` and returns the previous one. See [`AtomicUsize::swap`].
+    #[inline]
+    pub fn swap(&self, id: Option<TaggedRuntimeID<Tag>>, order: Ordering) -> Option<TaggedRuntimeID<Tag>> {
+        to_option(self.id.swap(from_option(id), order))
+    }
+
+    /// Replaces the ID held with `new` if it is `current`. Returns the previous ID, as `Ok` if it was replaced. See
+    /// [`AtomicUsize::compare_exchange`].
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: Option<TaggedRuntimeID<Tag>>,
+        new: Option<TaggedRuntimeID<Tag>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<TaggedRuntimeID<Tag>>, Option<TaggedRuntimeID<Tag>>> {
+        self.id
+            .compare_exchange(from_option(current), from_option(new), success, failure)
+            .map(to_option)
+            .map_err(to_option)
+    }
+
+    /// Like [`compare_exchange`](Self::compare_exchange), but may fail spuriously. See
+    /// [`AtomicUsize::compare_exchange_weak`].
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: Option<TaggedRuntimeID<Tag>>,
+        new: Option<TaggedRuntimeID<Tag>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<TaggedRuntimeID<Tag>>, Option<TaggedRuntimeID<Tag>>> {
+        self.id
+            .compare_exchange_weak(from_option(current), from_option(new), success, failure)
+            .map(to_option)
+            .map_err(to_option)
+    }
+
+    /// Replaces the ID held with the one `f` returns for it, retrying if another thread changed it in between.
+    /// Returns the previous ID, as `Err` if `f` returned `None`. See [`AtomicUsize::fetch_update`].
+    #[inline]
+    pub fn fetch_update(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: impl FnMut(Option<TaggedRuntimeID<Tag>>) -> Option<Option<TaggedRuntimeID<Tag>>>,
+    ) -> Result<Option<TaggedRuntimeID<Tag>>, Option<TaggedRuntimeID<Tag>>> {
+        self.id
+            .fetch_update(set_order, fetch_order, |raw| f(to_option(raw)).map(from_option))
+            .map(to_option)
+            .map_err(to_option)
+    }
+}
+
+impl<Tag> From<Option<TaggedRuntimeID<Tag>>> for AtomicOptionRuntimeID<Tag> {
+    #[inline]
+    fn from(id: Option<TaggedRuntimeID<Tag>>) -> Self {
+        Self::new(id)
+    }
+}
+
+impl<Tag> From<TaggedRuntimeID<Tag>> for AtomicOptionRuntimeID<Tag> {
+    #[inline]
+    fn from(id: TaggedRuntimeID<Tag>) -> Self {
+        Self::new(Some(id))
+    }
+}
+
+/// Holds `None`.
+impl<Tag> Default for AtomicOptionRuntimeID<Tag> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<Tag> fmt::Debug for AtomicOptionRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use crate::{AtomicOptionRuntimeID, AtomicRuntimeID, RuntimeID, TaggedRuntimeID};
+    use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed};
+    use std::collections::HashSet;
+    use std::vec::Vec;
+
+    #[test]
+    fn basic() {
+        let [a, b, c] = [RuntimeID::new(), RuntimeID::new(), RuntimeID::new()];
+        let cell = AtomicRuntimeID::new(a);
+        assert_eq!(cell.load(Relaxed), a);
+        cell.store(b, Relaxed);
+        assert_eq!(cell.swap(c, Relaxed), b);
+
+        assert_eq!(cell.compare_exchange(a, b, AcqRel, Acquire), Err(c));
+        assert_eq!(cell.compare_exchange(c, a, AcqRel, Acquire), Ok(c));
+        while cell.compare_exchange_weak(a, b, AcqRel, Acquire).is_err() {}
+        assert_eq!(cell.fetch_update(AcqRel, Acquire, |_| None), Err(b));
+        assert_eq!(cell.fetch_update(AcqRel, Acquire, |id| Some(id.max(c))), Ok(b));
+        assert_eq!(cell.into_inner(), c);
+    }
+
+    #[test]
+    fn option() {
+        let [a, b] = [RuntimeID::new(), RuntimeID::new()];
+        let cell = AtomicOptionRuntimeID::default();
+        assert_eq!(cell.load(Relaxed), None);
+        assert_eq!(cell.compare_exchange(Some(a), Some(b), AcqRel, Acquire), Err(None));
+        assert_eq!(cell.compare_exchange(None, Some(a), AcqRel, Acquire), Ok(None));
+        assert_eq!(cell.swap(None, Relaxed), Some(a));
+        cell.store(Some(b), Relaxed);
+        while cell.compare_exchange_weak(Some(b), None, AcqRel, Acquire).is_err() {}
+        assert_eq!(cell.fetch_update(AcqRel, Acquire, |id| Some(id.or(Some(a)))), Ok(None));
+        assert_eq!(cell.fetch_update(AcqRel, Acquire, |_| None), Err(Some(a)));
+        assert_eq!(cell.into_inner(), Some(a));
+        assert_eq!(AtomicOptionRuntimeID::from(b).into_inner(), Some(b));
+    }
+
+    #[test]
+    fn tagged() {
+        struct Session;
+
+        let id: TaggedRuntimeID<Session> = TaggedRuntimeID::new();
+        let cell = AtomicRuntimeID::new(id);
+        assert_eq!(cell.load(Relaxed), id);
+        static CURRENT: AtomicOptionRuntimeID<Session> = AtomicOptionRuntimeID::new(None);
+        assert_eq!(CURRENT.swap(Some(id), AcqRel), None);
+        assert_eq!(CURRENT.load(Acquire), Some(id));
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(core::mem::size_of::<AtomicRuntimeID>(), core::mem::size_of::<usize>());
+        assert_eq!(core::mem::size_of::<AtomicOptionRuntimeID>(), core::mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn debug() {
+        let id = RuntimeID::new();
+        assert_eq!(std::format!("{:?}", AtomicRuntimeID::new(id)), std::format!("{id:?}"));
+        assert_eq!(std::format!("{:?}", AtomicOptionRuntimeID::new(Some(id))), std::format!("{:?}", Some(id)));
+        assert_eq!(std::format!("{:?}", AtomicOptionRuntimeID::<()>::new(None)), "None");
+    }
+
+    /// Swapping hands each ID to exactly one thread, so none is lost or duplicated.
+    #[test]
+    fn swap_hands_over() {
+        let first = RuntimeID::new();
+        let cell = AtomicRuntimeID::new(first);
+        let mut seen: Vec<RuntimeID> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| (0..1000).map(|_| cell.swap(RuntimeID::new(), AcqRel)).collect::<Vec<_>>()))
+                .collect();
+            threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+        });
+        seen.push(cell.load(Acquire));
+
+        assert_eq!(seen.len(), 4001);
+        assert!(seen.contains(&first));
+        assert_eq!(seen.iter().collect::<HashSet<_>>().len(), seen.len());
+    }
+}
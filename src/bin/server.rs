@@ -0,0 +1,68 @@
+//! Hands out blocks of RuntimeIDs over a Unix domain socket. See `runtime_id::lease`.
+
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: runtime_id_server <socket> [--lease-ms <milliseconds>] [--persist <file> [--block <size>]]";
+
+#[cfg(unix)]
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(unix)]
+fn run(args: Vec<String>) -> Result<(), String> {
+    use runtime_id::lease::LeaseServer;
+    use std::time::Duration;
+
+    let mut socket = None;
+    let mut lease_ms = 30_000;
+    let mut persist: Option<String> = None;
+    let mut block: u64 = 1 << 16;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value\n{USAGE}"));
+        match arg.as_str() {
+            "--lease-ms" => lease_ms = value()?.parse().map_err(|error| format!("invalid --lease-ms: {error}"))?,
+            "--persist" => persist = Some(value()?),
+            "--block" => block = value()?.parse().map_err(|error| format!("invalid --block: {error}"))?,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if socket.is_none() && !arg.starts_with('-') => socket = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}\n{USAGE}")),
+        }
+    }
+    let socket = socket.ok_or(USAGE)?;
+    if block == 0 {
+        return Err("--block must be at least one".into());
+    }
+
+    let lease_duration = Duration::from_millis(lease_ms);
+    let server = match persist {
+        #[cfg(feature = "persist")]
+        Some(file) => {
+            let counter = runtime_id::persist::PersistentCounter::open(&file, block)
+                .map_err(|error| format!("failed to open {file}: {error}"))?;
+            LeaseServer::bind_persistent(&socket, lease_duration, counter)
+        }
+        #[cfg(not(feature = "persist"))]
+        Some(_) => return Err("--persist needs the persist feature".into()),
+        None => LeaseServer::bind(&socket, lease_duration),
+    };
+    let server = server.map_err(|error| format!("failed to listen on {socket}: {error}"))?;
+    server.run().map_err(|error| format!("failed to accept connections: {error}"))
+}
+
+#[cfg(not(unix))]
+fn main() -> ExitCode {
+    eprintln!("runtime_id_server needs Unix domain sockets");
+    ExitCode::FAILURE
+}
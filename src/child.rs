@@ -0,0 +1,57 @@
+//! Helpers for tests that run themselves again in child processes, to check behavior across processes.
+//!
+//! A test calls [`command`] with its own name to start a copy of the test binary that runs only that test, then
+//! checks [`arg`] at its start to find out whether it's the copy. Children report IDs with [`print_ids`] and the
+//! parent reads them back with [`ids`]. Files the processes share go at a [`temp_path`].
+
+// Not every feature combination has a test that uses every helper.
+#![allow(dead_code)]
+
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::string::String;
+use std::vec::Vec;
+
+const CHILD_ENV: &str = "RUNTIME_ID_TEST_CHILD";
+
+/// Returns a path in the temporary directory that no other test, in this process or another, uses.
+pub(crate) fn temp_path(prefix: &str) -> PathBuf {
+    let name = std::format!("runtime_id-{prefix}-{}-{}", std::process::id(), crate::RuntimeID::new());
+    std::env::temp_dir().join(name)
+}
+
+/// Returns the argument given to [`command`] if this process is a child started by it.
+pub(crate) fn arg() -> Option<OsString> {
+    std::env::var_os(CHILD_ENV)
+}
+
+/// Builds a command running only `test` (its path within the crate, like `shared::test::name`) in a new process,
+/// where [`arg`] returns `arg`. Its stdout is piped and its stderr discarded.
+pub(crate) fn command(test: &str, arg: impl AsRef<OsStr>) -> Command {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command
+        .args(["--exact", test, "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, arg)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    command
+}
+
+/// Writes `ids` to stdout for the parent to read with [`ids`].
+pub(crate) fn print_ids(ids: impl IntoIterator<Item = u64>) {
+    let mut out = std::io::stdout().lock();
+    for id in ids {
+        writeln!(out, "id:{id}").unwrap();
+    }
+    out.flush().unwrap();
+}
+
+/// Returns the IDs a child wrote with [`print_ids`], in order.
+pub(crate) fn ids(output: &Output) -> Vec<u64> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // The test harness may print its own text at the start of the first line.
+    let lines = stdout.lines().filter_map(|line| line.rsplit_once("id:"));
+    lines.map(|(_, id)| id.parse().unwrap()).collect()
+}
@@ -0,0 +1,182 @@
+use crate::exhaustion::{exhaustion_policy, Exhausted, ExhaustionPolicy, LIMIT};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counter backing an independent sequence of IDs.
+///
+/// Each domain counts up from zero on its own, so IDs from one domain form a dense sequence that can be used as
+/// indices. Most code should use [`define_runtime_id!`](crate::define_runtime_id) rather than a domain directly.
+///
+/// # Example
+/// ```
+/// use runtime_id::IdDomain;
+///
+/// static TEXTURES: IdDomain = IdDomain::new();
+///
+/// assert_eq!(TEXTURES.next_id(), 0);
+/// assert_eq!(TEXTURES.next_id(), 1);
+/// ```
+pub struct IdDomain {
+    next: AtomicUsize,
+}
+
+impl IdDomain {
+    /// Creates a domain whose first ID is zero.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self::starting_at(0)
+    }
+
+    pub(crate) const fn starting_at(first: usize) -> Self {
+        IdDomain { next: AtomicUsize::new(first) }
+    }
+
+    /// Takes the next ID from this domain.
+    ///
+    /// If the domain is exhausted the current [`ExhaustionPolicy`] decides what happens.
+    #[inline]
+    pub fn next_id(&self) -> usize {
+        self.next_id_with(exhaustion_policy())
+    }
+
+    /// Takes the next ID from this domain, or returns [`Exhausted`] once all `isize::MAX` IDs have been handed out.
+    #[inline]
+    pub fn try_next_id(&self) -> Result<usize, Exhausted> {
+        self.try_take(Ordering::Relaxed)
+    }
+
+    /// Takes the next ID, using `ordering` for the increment so callers can synchronize through the counter.
+    #[inline]
+    pub(crate) fn try_take(&self, ordering: Ordering) -> Result<usize, Exhausted> {
+        let id = self.next.fetch_add(1, ordering);
+        if id > LIMIT {
+            // Pull the counter back so it can't wrap around, however many threads keep failing.
+            self.next.store(LIMIT + 1, Ordering::Relaxed);
+            return Err(Exhausted);
+        }
+        Ok(id)
+    }
+
+    /// Takes `count` consecutive IDs from this domain at once and returns the first of them, or returns
+    /// [`Exhausted`] if fewer than `count` IDs are left.
+    ///
+    /// # Example
+    /// ```
+    /// use runtime_id::IdDomain;
+    ///
+    /// let domain = IdDomain::new();
+    /// assert_eq!(domain.try_reserve(10), Ok(0));
+    /// assert_eq!(domain.next_id(), 10);
+    /// ```
+    pub fn try_reserve(&self, count: usize) -> Result<usize, Exhausted> {
+        self.next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                next.checked_add(count).filter(|&end| end <= LIMIT + 1)
+            })
+            .map_err(|_| Exhausted)
+    }
+
+    /// Returns true if `id` has been handed out or reserved.
+    #[inline]
+    pub(crate) fn has_issued(&self, id: usize) -> bool {
+        id < self.next.load(Ordering::Relaxed).min(LIMIT + 1)
+    }
+
+    /// Takes consecutive IDs below `ceiling` and returns them as `first..end`: one `share`th of the IDs left below
+    /// `ceiling`, but no more than `max`.
+    #[cfg(all(feature = "fork-safe", unix))]
+    pub(crate) fn split_off(&self, share: usize, max: usize, ceiling: usize) -> (usize, usize) {
+        let ceiling = ceiling.min(LIMIT + 1);
+        let mut block = (0, 0);
+        let _ = self.next.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+            let size = max.min(ceiling.saturating_sub(next) / share);
+            block = (next, next + size);
+            Some(next + size)
+        });
+        block
+    }
+
+    /// Moves the counter so the next ID handed out is `next`.
+    #[cfg(all(feature = "fork-safe", unix))]
+    pub(crate) fn restart_at(&self, next: usize) {
+        self.next.store(next, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn next_id_with(&self, policy: ExhaustionPolicy) -> usize {
+        match self.try_next_id() {
+            Ok(id) => id,
+            Err(Exhausted) => policy.handle(LIMIT),
+        }
+    }
+}
+
+/// Declares a new ID type with its own counter.
+///
+/// The generated type is `Copy`, `Eq` and `Hash` like [`RuntimeID`](crate::RuntimeID), but draws from a private
+/// [`IdDomain`] so its IDs start at zero and stay dense regardless of how many other IDs the program creates.
+///
+/// # Example
+/// ```
+/// runtime_id::define_runtime_id!(
+///     /// Identifies an open connection.
+///     pub ConnId
+/// );
+/// runtime_id::define_runtime_id!(SessionId);
+///
+/// let a = ConnId::new();
+/// let b = ConnId::new();
+/// assert_ne!(a, b);
+/// assert_eq!(b.index(), a.index() + 1);
+/// assert_eq!(SessionId::new().index(), 0);
+/// ```
+#[macro_export]
+macro_rules! define_runtime_id {
+    ($(#[$meta:meta])* $vis:vis $name:ident) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        $vis struct $name(usize);
+
+        impl $name {
+            /// Creates a new ID unique within this type.
+            #[inline]
+            #[allow(clippy::new_without_default)]
+            pub fn new() -> Self {
+                static DOMAIN: $crate::IdDomain = $crate::IdDomain::new();
+                $name(DOMAIN.next_id())
+            }
+
+            /// Returns this ID's position in its type's sequence, starting at zero.
+            #[inline]
+            pub const fn index(self) -> usize {
+                self.0
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IdDomain;
+
+    #[test]
+    fn independent_domains() {
+        crate::define_runtime_id!(ConnId);
+        crate::define_runtime_id!(SessionId);
+
+        let conns = [ConnId::new(), ConnId::new(), ConnId::new()];
+        let session = SessionId::new();
+
+        assert_eq!(conns.map(ConnId::index), [0, 1, 2]);
+        assert_eq!(session.index(), 0);
+        assert_ne!(conns[0], conns[1]);
+    }
+
+    #[test]
+    fn domain_is_dense() {
+        let domain = IdDomain::new();
+        for expected in 0..1000 {
+            assert_eq!(domain.next_id(), expected);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Textual encodings for [`RuntimeID`](crate::RuntimeID).
+//!
+//! `Display` and `FromStr` use decimal, and `LowerHex`/`UpperHex` use hexadecimal. Other encodings are chosen with
+//! [`RuntimeID::encode`](TaggedRuntimeID::encode) and [`RuntimeID::decode`](TaggedRuntimeID::decode).
+
+use crate::TaggedRuntimeID;
+use core::fmt;
+use core::marker::PhantomData;
+use core::str::FromStr;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A textual encoding for IDs. Implemented by [`Decimal`], [`Hex`], [`Base32`] and [`Base62`].
+pub trait Encoding: sealed::Sealed {
+    #[doc(hidden)]
+    fn write(value: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    #[doc(hidden)]
+    fn read(text: &str) -> Result<usize, ParseIdError>;
+}
+
+/// Base 10, the same as `Display`.
+pub struct Decimal;
+
+/// Base 16 using lowercase letters. Parsing also accepts uppercase.
+pub struct Hex;
+
+/// Crockford's base 32, which skips easily confused letters. Parsing is case-insensitive and reads `I` and `L` as `1`
+/// and `O` as `0`.
+pub struct Base32;
+
+/// Base 62 using digits, uppercase and then lowercase letters. This is the most compact encoding.
+pub struct Base62;
+
+const HEX: &[u8] = b"0123456789abcdef";
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+impl sealed::Sealed for Decimal {}
+impl Encoding for Decimal {
+    fn write(value: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&value, f)
+    }
+
+    fn read(text: &str) -> Result<usize, ParseIdError> {
+        read_radix(text, 10, |digit| (digit as char).to_digit(10))
+    }
+}
+
+impl sealed::Sealed for Hex {}
+impl Encoding for Hex {
+    fn write(value: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_radix(value, HEX, f)
+    }
+
+    fn read(text: &str) -> Result<usize, ParseIdError> {
+        read_radix(text, 16, |digit| (digit as char).to_digit(16))
+    }
+}
+
+impl sealed::Sealed for Base32 {}
+impl Encoding for Base32 {
+    fn write(value: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_radix(value, CROCKFORD, f)
+    }
+
+    fn read(text: &str) -> Result<usize, ParseIdError> {
+        read_radix(text, 32, |digit| match digit.to_ascii_uppercase() {
+            b'O' => Some(0),
+            b'I' | b'L' => Some(1),
+            digit => CROCKFORD.iter().position(|&c| c == digit).map(|value| value as u32),
+        })
+    }
+}
+
+impl sealed::Sealed for Base62 {}
+impl Encoding for Base62 {
+    fn write(value: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_radix(value, BASE62, f)
+    }
+
+    fn read(text: &str) -> Result<usize, ParseIdError> {
+        read_radix(text, 62, |digit| BASE62.iter().position(|&c| c == digit).map(|value| value as u32))
+    }
+}
+
+fn write_radix(mut value: usize, alphabet: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let radix = alphabet.len();
+    let mut buffer = [0; usize::BITS as usize];
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = alphabet[value % radix];
+        value /= radix;
+        if value == 0 {
+            break;
+        }
+    }
+    // The alphabets are all ASCII.
+    f.pad(core::str::from_utf8(&buffer[start..]).map_err(|_| fmt::Error)?)
+}
+
+fn read_radix(text: &str, radix: u32, digit: impl Fn(u8) -> Option<u32>) -> Result<usize, ParseIdError> {
+    if text.is_empty() {
+        return Err(ParseIdError(ParseIdErrorKind::Empty));
+    }
+    text.bytes().try_fold(0usize, |value, byte| {
+        let digit = digit(byte).ok_or(ParseIdError(ParseIdErrorKind::InvalidDigit))?;
+        value
+            .checked_mul(radix as usize)
+            .and_then(|value| value.checked_add(digit as usize))
+            .ok_or(ParseIdError(ParseIdErrorKind::NotIssued))
+    })
+}
+
+/// Error returned when parsing an ID fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseIdError(ParseIdErrorKind);
+
+/// Why parsing an ID failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseIdErrorKind {
+    /// The text was empty.
+    Empty,
+    /// The text contained a character that isn't part of the encoding.
+    InvalidDigit,
+    /// The text was well formed, but no such ID has been created by this run of the program.
+    NotIssued,
+}
+
+impl ParseIdError {
+    /// Returns why parsing failed.
+    pub fn kind(&self) -> ParseIdErrorKind {
+        self.0
+    }
+}
+
+impl fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.0 {
+            ParseIdErrorKind::Empty => "cannot parse RuntimeID from empty string",
+            ParseIdErrorKind::InvalidDigit => "invalid digit found in RuntimeID",
+            ParseIdErrorKind::NotIssued => "RuntimeID was not issued by this run of the program",
+        })
+    }
+}
+
+impl core::error::Error for ParseIdError {}
+
+/// An ID formatted with a chosen [`Encoding`], returned by [`RuntimeID::encode`](TaggedRuntimeID::encode).
+pub struct Encoded<E, Tag = ()> {
+    id: TaggedRuntimeID<Tag>,
+    encoding: PhantomData<fn() -> E>,
+}
+
+impl<E: Encoding, Tag> fmt::Display for Encoded<E, Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        E::write(self.id.id.get(), f)
+    }
+}
+
+impl<Tag> TaggedRuntimeID<Tag> {
+    /// Returns a value that displays this ID using the encoding `E`.
+    ///
+    /// # Example
+    /// ```
+    /// use runtime_id::encode::Base62;
+    /// use runtime_id::RuntimeID;
+    ///
+    /// let id = RuntimeID::new();
+    /// let text = id.encode::<Base62>().to_string();
+    /// assert_eq!(RuntimeID::decode::<Base62>(&text), Ok(id));
+    /// ```
+    #[inline]
+    pub fn encode<E: Encoding>(self) -> Encoded<E, Tag> {
+        Encoded { id: self, encoding: PhantomData }
+    }
+
+    /// Parses an ID written with the encoding `E`.
+    ///
+    /// Parsing only succeeds for IDs this run of the program has already created, including IDs reserved in bulk
+    /// that haven't been handed out yet.
+    pub fn decode<E: Encoding>(text: &str) -> Result<Self, ParseIdError> {
+        let id = E::read(text)?;
+        if id == 0 || !crate::has_issued(id) {
+            return Err(ParseIdError(ParseIdErrorKind::NotIssued));
+        }
+        Ok(Self::from_counter(id))
+    }
+}
+
+impl<Tag> fmt::Display for TaggedRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Decimal::write(self.id.get(), f)
+    }
+}
+
+impl<Tag> fmt::LowerHex for TaggedRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.id, f)
+    }
+}
+
+impl<Tag> fmt::UpperHex for TaggedRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.id, f)
+    }
+}
+
+impl<Tag> FromStr for TaggedRuntimeID<Tag> {
+    type Err = ParseIdError;
+
+    /// Parses a decimal ID. See [`decode`](Self::decode).
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::decode::<Decimal>(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::{Base32, Base62, Decimal, Encoding, Hex, ParseIdErrorKind};
+    use crate::RuntimeID;
+    use std::format;
+    use std::string::{String, ToString};
+
+    fn encode<E: Encoding>(value: usize) -> String {
+        struct Show<E>(usize, core::marker::PhantomData<E>);
+        impl<E: Encoding> core::fmt::Display for Show<E> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                E::write(self.0, f)
+            }
+        }
+        Show::<E>(value, core::marker::PhantomData).to_string()
+    }
+
+    #[test]
+    fn known_values() {
+        assert_eq!(encode::<Decimal>(12345), "12345");
+        assert_eq!(encode::<Hex>(0xbeef), "beef");
+        assert_eq!(encode::<Base32>(32 * 32 - 1), "ZZ");
+        assert_eq!(encode::<Base62>(61), "z");
+        assert_eq!(encode::<Base62>(62), "10");
+        assert_eq!(Base62::read(&encode::<Base62>(usize::MAX)), Ok(usize::MAX));
+        assert_eq!(Base32::read("zz"), Ok(32 * 32 - 1));
+        assert_eq!(Base32::read("IlO"), Ok(32 * 32 + 32));
+        assert_eq!(Hex::read("BEEF"), Ok(0xbeef));
+    }
+
+    #[test]
+    fn round_trip() {
+        for id in RuntimeID::reserve(1000) {
+            assert_eq!(id.to_string().parse(), Ok(id));
+            assert_eq!(RuntimeID::decode::<Hex>(&format!("{:x}", id)), Ok(id));
+            assert_eq!(RuntimeID::decode::<Hex>(&format!("{:X}", id)), Ok(id));
+            assert_eq!(RuntimeID::decode::<Hex>(&id.encode::<Hex>().to_string()), Ok(id));
+            assert_eq!(RuntimeID::decode::<Base32>(&id.encode::<Base32>().to_string()), Ok(id));
+            assert_eq!(RuntimeID::decode::<Base62>(&id.encode::<Base62>().to_string()), Ok(id));
+        }
+    }
+
+    #[test]
+    fn formatter_flags() {
+        let id = RuntimeID::new();
+        assert_eq!(format!("{:>30}", id).len(), 30);
+        assert_eq!(format!("{:>30}", id.encode::<Base62>()).len(), 30);
+        assert_eq!(format!("{:#x}", id), format!("0x{:x}", id));
+    }
+
+    #[test]
+    fn errors() {
+        let kind = |text: &str| text.parse::<RuntimeID>().unwrap_err().kind();
+        assert_eq!(kind(""), ParseIdErrorKind::Empty);
+        assert_eq!(kind("12a"), ParseIdErrorKind::InvalidDigit);
+        assert_eq!(kind("-1"), ParseIdErrorKind::InvalidDigit);
+        assert_eq!(kind("0"), ParseIdErrorKind::NotIssued);
+        assert_eq!(kind("99999999999999999999999999"), ParseIdErrorKind::NotIssued);
+
+        let unissued = RuntimeID::new().id.get() + 1_000_000;
+        assert_eq!(kind(&unissued.to_string()), ParseIdErrorKind::NotIssued);
+    }
+}
@@ -0,0 +1,159 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The largest value a counter will hand out.
+///
+/// Capping counters at `isize::MAX` leaves the upper half of `usize` as headroom, so a counter that is pushed past
+/// its limit by many threads at once can be pulled back before it ever wraps around and reissues old IDs.
+pub(crate) const LIMIT: usize = isize::MAX as usize;
+
+/// The largest value a 64-bit counter will hand out, leaving the same headroom as [`LIMIT`].
+pub(crate) const LIMIT64: u64 = i64::MAX as u64;
+
+static POLICY: AtomicU8 = AtomicU8::new(ExhaustionPolicy::Panic as u8);
+
+/// Error returned when a counter has no IDs left to hand out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exhausted;
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RuntimeID space exhausted")
+    }
+}
+
+impl core::error::Error for Exhausted {}
+
+/// What infallible constructors such as [`RuntimeID::new`](crate::TaggedRuntimeID::new) do once their counter is
+/// exhausted.
+///
+/// Fallible constructors such as [`RuntimeID::try_new`](crate::TaggedRuntimeID::try_new) always return
+/// [`Exhausted`] instead. The policy is process wide and set with [`set_exhaustion_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ExhaustionPolicy {
+    /// Panic. This is the default.
+    #[default]
+    Panic = 0,
+    /// Abort the process, even if panics would otherwise unwind.
+    Abort = 1,
+    /// Keep returning the last ID of the counter. IDs are no longer unique once this happens, but the counter never
+    /// wraps around to reissue earlier IDs.
+    Saturate = 2,
+}
+
+impl ExhaustionPolicy {
+    /// Applies the policy to an exhausted counter whose last ID was `last`.
+    #[cold]
+    pub(crate) fn handle<T>(self, last: T) -> T {
+        match self {
+            ExhaustionPolicy::Panic => panic!("{}", Exhausted),
+            ExhaustionPolicy::Abort => abort(),
+            ExhaustionPolicy::Saturate => last,
+        }
+    }
+}
+
+/// Sets the process wide [`ExhaustionPolicy`].
+pub fn set_exhaustion_policy(policy: ExhaustionPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Returns the current process wide [`ExhaustionPolicy`].
+pub fn exhaustion_policy() -> ExhaustionPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        1 => ExhaustionPolicy::Abort,
+        2 => ExhaustionPolicy::Saturate,
+        _ => ExhaustionPolicy::Panic,
+    }
+}
+
+/// Aborts without relying on `std`: a panic raised while unwinding from another panic always aborts.
+#[cold]
+fn abort() -> ! {
+    struct Abort;
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            panic!("{}", Exhausted);
+        }
+    }
+
+    let _abort = Abort;
+    panic!("{}", Exhausted);
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::LIMIT;
+    use crate::{exhaustion_policy, set_exhaustion_policy, Exhausted, ExhaustionPolicy, IdDomain};
+
+    #[test]
+    fn try_next_stops_at_limit() {
+        let domain = IdDomain::starting_at(LIMIT - 1);
+        assert_eq!(domain.try_next_id(), Ok(LIMIT - 1));
+        assert_eq!(domain.try_next_id(), Ok(LIMIT));
+        for _ in 0..1000 {
+            assert_eq!(domain.try_next_id(), Err(Exhausted));
+        }
+    }
+
+    #[test]
+    fn try_reserve_stops_at_limit() {
+        let domain = IdDomain::starting_at(LIMIT - 9);
+        assert_eq!(domain.try_reserve(11), Err(Exhausted));
+        assert_eq!(domain.try_reserve(10), Ok(LIMIT - 9));
+        assert_eq!(domain.try_reserve(1), Err(Exhausted));
+        assert_eq!(domain.try_next_id(), Err(Exhausted));
+    }
+
+    #[test]
+    #[should_panic(expected = "RuntimeID space exhausted")]
+    fn panic_policy() {
+        let domain = IdDomain::starting_at(LIMIT);
+        assert_eq!(domain.next_id_with(ExhaustionPolicy::Panic), LIMIT);
+        domain.next_id_with(ExhaustionPolicy::Panic);
+    }
+
+    #[test]
+    fn saturate_policy() {
+        let domain = IdDomain::starting_at(LIMIT);
+        for _ in 0..1000 {
+            assert_eq!(domain.next_id_with(ExhaustionPolicy::Saturate), LIMIT);
+        }
+        assert_eq!(domain.try_next_id(), Err(Exhausted));
+    }
+
+    #[test]
+    fn abort_policy() {
+        if std::env::var_os("RUNTIME_ID_ABORT_CHILD").is_some() {
+            let domain = IdDomain::starting_at(LIMIT);
+            domain.next_id_with(ExhaustionPolicy::Abort);
+            domain.next_id_with(ExhaustionPolicy::Abort);
+            unreachable!();
+        }
+
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "exhaustion::test::abort_policy", "--test-threads=1"])
+            .env("RUNTIME_ID_ABORT_CHILD", "1")
+            .stderr(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            assert_eq!(status.signal(), Some(6));
+        }
+    }
+
+    #[test]
+    fn global_policy() {
+        assert_eq!(exhaustion_policy(), ExhaustionPolicy::Panic);
+        set_exhaustion_policy(ExhaustionPolicy::Saturate);
+        assert_eq!(exhaustion_policy(), ExhaustionPolicy::Saturate);
+        set_exhaustion_policy(ExhaustionPolicy::Panic);
+    }
+}
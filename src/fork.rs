@@ -0,0 +1,215 @@
+//! Gives child processes created with `fork` an ID space of their own.
+//!
+//! A forked child starts with a copy of its parent's memory, counter included, so without help both go on to create
+//! the same IDs. With the `fork-safe` feature, every `fork` first takes a block of IDs from the parent's counter and
+//! hands it to the child, whose counter then only covers that block. The parent carries on after the block, so
+//! neither process takes an ID from the counter that the other takes too. The child also forgets the rest of the
+//! forking thread's [`new_local`](crate::TaggedRuntimeID::new_local) block, which the parent keeps using, and gets a
+//! new [`run_token`](crate::run_token).
+//!
+//! IDs taken from the counter before the fork but not yet used are the exception. An
+//! [`IdRange`](crate::IdRange) from [`RuntimeID::reserve`](crate::TaggedRuntimeID::reserve) is copied into the child
+//! along with the rest of its memory, so both processes go on to hand out the same IDs from it. Reserve ranges after
+//! forking, in the process that uses them.
+//!
+//! A block holds a sixty-fourth of the IDs the forking process has left, but no more than `2^(usize::BITS / 2)`.
+//! On 64-bit targets a child of the original process gets the full `2^32` IDs and the parent can fork billions of
+//! times. On 32-bit targets the first child gets `2^25` IDs, later children get a little less each time, and the
+//! parent's own share shrinks by a sixty-fourth with every fork, even one followed straight away by `exec`. Once a
+//! process has used up its share it is [exhausted](crate::Exhausted), and forking again takes from it the same way.
+//!
+//! This covers IDs from the global counter behind [`RuntimeID::new`](crate::TaggedRuntimeID::new) and its
+//! variants; [`RuntimeID64`](crate::RuntimeID64), [`IdDomain`](crate::IdDomain)s and the other counters still
+//! continue from the same value in both processes.
+//!
+//! The handlers are installed by a constructor when the program starts, so even a process that forks before
+//! creating any ID is covered.
+
+use crate::exhaustion::Exhausted;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The most IDs handed to a child.
+const CHILD_BLOCK: usize = 1 << (usize::BITS / 2);
+
+/// A child gets at most this fraction of the IDs its parent has left.
+const CHILD_SHARE: usize = 64;
+
+/// The end of this process's share of the global counter. IDs at or above it belong to the parent.
+static CEILING: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+std::thread_local! {
+    /// The block split off for the child by the fork in progress on this thread, as `first..end`. Fork handlers
+    /// run on the forking thread, so concurrent forks on other threads can't mix their blocks up.
+    static PENDING: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Fails if the `count` IDs starting at `first` reach past this process's share of the counter.
+#[inline]
+pub(crate) fn check(first: usize, count: usize) -> Result<(), Exhausted> {
+    match first.checked_add(count) {
+        Some(end) if end <= CEILING.load(Ordering::Relaxed) => Ok(()),
+        _ => Err(Exhausted),
+    }
+}
+
+#[used]
+#[cfg_attr(target_vendor = "apple", link_section = "__DATA,__mod_init_func")]
+#[cfg_attr(not(target_vendor = "apple"), link_section = ".init_array")]
+static REGISTER: extern "C" fn() = register;
+
+extern "C" fn register() {
+    // SAFETY: the handlers only touch atomics and constant-initialized thread locals, which is safe in a forked
+    // child.
+    let result = unsafe { libc::pthread_atfork(Some(prepare), None, Some(child)) };
+    assert_eq!(result, 0, "failed to register fork handlers");
+}
+
+extern "C" fn prepare() {
+    let block = crate::ID.split_off(CHILD_SHARE, CHILD_BLOCK, CEILING.load(Ordering::Relaxed));
+    let _ = PENDING.try_with(|pending| pending.set(block));
+}
+
+extern "C" fn child() {
+    let (first, end) = PENDING.try_with(Cell::get).unwrap_or((0, 0));
+    crate::ID.restart_at(first);
+    CEILING.store(end, Ordering::Relaxed);
+    crate::local::forget_block();
+    crate::run::reset();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CHILD_BLOCK, CHILD_SHARE};
+    use crate::domain::IdDomain;
+    use crate::{run_token, RuntimeID};
+    use std::collections::HashSet;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+    use std::vec::Vec;
+
+    /// Forks, runs `child` in the child process and returns what it wrote.
+    fn in_child(child: impl FnOnce() -> Vec<u8>) -> Vec<u8> {
+        let mut fds = [0; 2];
+        // SAFETY: plain libc calls; the child only runs `child` and then exits without unwinding.
+        unsafe {
+            assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                libc::close(fds[0]);
+                // A panic must not unwind into the copy of the test harness running in the child.
+                let Ok(output) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(child)) else { libc::_exit(1) };
+                libc::write(fds[1], output.as_ptr().cast(), output.len());
+                libc::_exit(0);
+            }
+
+            libc::close(fds[1]);
+            let mut output = Vec::new();
+            std::fs::File::from_raw_fd(fds[0]).read_to_end(&mut output).unwrap();
+            let mut status = 0;
+            assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+            assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+            output
+        }
+    }
+
+    fn to_bytes(ids: impl IntoIterator<Item = RuntimeID>) -> Vec<u8> {
+        ids.into_iter().flat_map(|id| id.id.get().to_ne_bytes()).collect()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Vec<usize> {
+        bytes.chunks(size_of::<usize>()).map(|id| usize::from_ne_bytes(id.try_into().unwrap())).collect()
+    }
+
+    #[test]
+    fn child_ids_are_disjoint() {
+        // Leave part of this thread's local block unused, for both processes to continue from.
+        let before = [RuntimeID::new(), RuntimeID::new_local()];
+        let create = || (0..1000).flat_map(|_| [RuntimeID::new(), RuntimeID::new_local()]).collect::<Vec<_>>();
+
+        let child = from_bytes(&in_child(|| to_bytes(create())));
+        let parent: Vec<usize> = create().into_iter().map(|id| id.id.get()).collect();
+
+        assert_eq!(child.len(), 2000);
+        assert!(child.iter().all(|&id| before.iter().all(|before| id > before.id.get())));
+        let parent: HashSet<usize> = parent.into_iter().collect();
+        assert!(child.iter().all(|id| !parent.contains(id)));
+    }
+
+    #[test]
+    fn child_block_is_bounded() {
+        let child = from_bytes(&in_child(|| {
+            let first = RuntimeID::new();
+            let rest = RuntimeID::try_reserve(CHILD_BLOCK - 1).is_ok();
+            let after = RuntimeID::try_new().is_err() && RuntimeID::try_reserve(1).is_err();
+            [first.id.get(), rest as usize, after as usize].iter().flat_map(|value| value.to_ne_bytes()).collect()
+        }));
+        let parent = RuntimeID::new().id.get();
+
+        assert_eq!(child[1..], [1, 1], "the child gets exactly one block");
+        assert!(parent >= child[0] + CHILD_BLOCK, "the parent continues after the child's block");
+    }
+
+    #[test]
+    fn block_shrinks_with_remaining_space() {
+        // The space a 32-bit process starts with.
+        let ceiling = 1 << 31;
+        let domain = IdDomain::starting_at(1);
+        let first = domain.split_off(CHILD_SHARE, CHILD_BLOCK, ceiling);
+        assert_eq!(first, (1, 1 + (ceiling - 1) / 64));
+        let second = domain.split_off(CHILD_SHARE, CHILD_BLOCK, ceiling);
+        assert_eq!(second.0, first.1);
+        assert!(second.1 - second.0 < first.1 - first.0);
+
+        let domain = IdDomain::starting_at(1);
+        assert_eq!(domain.split_off(CHILD_SHARE, 1 << 16, ceiling), (1, 1 + (1 << 16)));
+        let (first, end) = domain.split_off(CHILD_SHARE, CHILD_BLOCK, 100);
+        assert_eq!(first, end, "nothing left to share");
+    }
+
+    #[test]
+    fn reserved_ranges_are_shared() {
+        let mut range = RuntimeID::reserve(3);
+        range.next();
+        let child = from_bytes(&in_child(|| to_bytes(range.clone())));
+        let parent: Vec<usize> = range.map(|id| id.id.get()).collect();
+        assert_eq!(child, parent, "a range reserved before forking is copied into the child");
+    }
+
+    #[test]
+    fn child_gets_new_token() {
+        let parent = run_token();
+        let child = in_child(|| run_token().to_ne_bytes().to_vec());
+        let child = u64::from_ne_bytes(child.try_into().unwrap());
+
+        assert_ne!(child, 0);
+        assert_ne!(child, parent);
+        assert_eq!(run_token(), parent);
+    }
+
+    #[test]
+    fn grandchild_gets_new_token() {
+        let parent = run_token();
+        let tokens = in_child(|| {
+            let child = run_token();
+            let grandchild = in_child(|| run_token().to_ne_bytes().to_vec());
+            [child.to_ne_bytes().to_vec(), grandchild].concat()
+        });
+        let child = u64::from_ne_bytes(tokens[..8].try_into().unwrap());
+        let grandchild = u64::from_ne_bytes(tokens[8..].try_into().unwrap());
+
+        assert_ne!(child, parent);
+        assert_ne!(grandchild, parent);
+        assert_ne!(grandchild, child);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn child_rejects_parent_ids() {
+        let json = serde_json::to_string(&crate::RuntimeID::new()).unwrap();
+        let accepted = in_child(|| std::vec![serde_json::from_str::<crate::RuntimeID>(&json).is_ok() as u8]);
+        assert_eq!(accepted, [0]);
+        assert!(serde_json::from_str::<crate::RuntimeID>(&json).is_ok());
+    }
+}
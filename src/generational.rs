@@ -0,0 +1,202 @@
+use crate::exhaustion::Exhausted;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::num::NonZeroU32;
+
+/// An ID whose index is recycled by an [`IdPool`], paired with a generation that tells reuses apart.
+///
+/// Indices stay dense, so they can be used to index vectors and bitsets that would otherwise grow without bound.
+/// Every time an index is reused its generation goes up, so an ID that has been released never compares equal to
+/// the ID that replaces it.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct GenerationalID {
+    index: u32,
+    generation: NonZeroU32,
+}
+
+impl GenerationalID {
+    /// Returns the slot this ID occupies in its pool.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+
+    /// Returns how many times this ID's index had been handed out when it was created, starting at one.
+    #[inline]
+    pub fn generation(self) -> u32 {
+        self.generation.get()
+    }
+}
+
+impl fmt::Debug for GenerationalID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GenerationalID({}v{})", self.index, self.generation)
+    }
+}
+
+impl Hash for GenerationalID {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(u64::from(self.generation.get()) << 32 | u64::from(self.index));
+    }
+}
+
+/// Allocator for [`GenerationalID`]s that reuses released indices.
+///
+/// Unlike [`RuntimeID`](crate::RuntimeID) a pool is a plain value, so IDs are only unique within the pool that
+/// created them. Pools aren't synchronized; share one between threads behind a lock.
+///
+/// # Example
+/// ```
+/// use runtime_id::IdPool;
+///
+/// let mut pool = IdPool::new();
+/// let a = pool.allocate();
+/// assert!(pool.release(a));
+///
+/// let b = pool.allocate();
+/// assert_eq!(a.index(), b.index());
+/// assert_ne!(a, b);
+/// assert!(!pool.is_live(a));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct IdPool {
+    /// The generation of each index's current occupant, or of its next occupant if the index is free. Retired
+    /// indices are zero, which no ID has as its generation.
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    retired: usize,
+}
+
+impl IdPool {
+    /// Creates an empty pool.
+    pub const fn new() -> Self {
+        IdPool { generations: Vec::new(), free: Vec::new(), retired: 0 }
+    }
+
+    /// Allocates an ID, reusing a released index if there is one.
+    ///
+    /// # Panics
+    /// Panics if all `u32::MAX + 1` indices are in use or retired.
+    pub fn allocate(&mut self) -> GenerationalID {
+        match self.try_allocate() {
+            Ok(id) => id,
+            Err(exhausted) => panic!("{}", exhausted),
+        }
+    }
+
+    /// Allocates an ID, or returns [`Exhausted`] if all `u32::MAX + 1` indices are in use or retired.
+    pub fn try_allocate(&mut self) -> Result<GenerationalID, Exhausted> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = u32::try_from(self.generations.len()).map_err(|_| Exhausted)?;
+                self.generations.push(1);
+                index
+            }
+        };
+
+        match NonZeroU32::new(self.generations[index as usize]) {
+            Some(generation) => Ok(GenerationalID { index, generation }),
+            None => unreachable!("retired indices are never on the free list"),
+        }
+    }
+
+    /// Releases `id` so its index can be reused. Returns false, and does nothing, if `id` isn't live.
+    ///
+    /// An index whose generation would overflow is retired instead of being reused, so a stale ID can never match a
+    /// live one.
+    pub fn release(&mut self, id: GenerationalID) -> bool {
+        if !self.is_live(id) {
+            return false;
+        }
+
+        match id.generation().checked_add(1) {
+            Some(next) => {
+                self.generations[id.index()] = next;
+                self.free.push(id.index);
+            }
+            None => {
+                self.generations[id.index()] = 0;
+                self.retired += 1;
+            }
+        }
+        true
+    }
+
+    /// Returns true if `id` was allocated by this pool and hasn't been released.
+    #[inline]
+    pub fn is_live(&self, id: GenerationalID) -> bool {
+        self.generations.get(id.index()) == Some(&id.generation())
+    }
+
+    /// Returns the number of live IDs.
+    pub fn len(&self) -> usize {
+        self.generations.len() - self.free.len() - self.retired
+    }
+
+    /// Returns true if no IDs are live.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns one more than the highest index this pool has handed out, which is the size needed for a table
+    /// indexed by [`GenerationalID::index`].
+    pub fn capacity(&self) -> usize {
+        self.generations.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GenerationalID;
+    use crate::IdPool;
+    use core::num::NonZeroU32;
+
+    #[test]
+    fn reuse() {
+        let mut pool = IdPool::new();
+        let a = pool.allocate();
+        let b = pool.allocate();
+        assert_eq!((a.index(), b.index()), (0, 1));
+        assert_eq!(pool.len(), 2);
+
+        assert!(pool.release(a));
+        assert!(!pool.release(a));
+        assert!(!pool.is_live(a));
+        assert_eq!(pool.len(), 1);
+
+        let c = pool.allocate();
+        assert_eq!(c.index(), a.index());
+        assert_eq!(c.generation(), a.generation() + 1);
+        assert_ne!(c, a);
+        assert!(pool.is_live(c));
+        assert!(!pool.is_live(a));
+        assert!(!pool.release(a));
+        assert_eq!(pool.capacity(), 2);
+    }
+
+    #[test]
+    fn niche() {
+        assert_eq!(core::mem::size_of::<Option<GenerationalID>>(), core::mem::size_of::<GenerationalID>());
+    }
+
+    #[test]
+    fn retire_on_generation_overflow() {
+        let mut pool = IdPool::new();
+        let id = pool.allocate();
+        pool.generations[id.index()] = u32::MAX;
+        let last = GenerationalID { index: id.index, generation: NonZeroU32::MAX };
+
+        assert!(!pool.is_live(id));
+        assert!(pool.release(last));
+        assert!(!pool.is_live(last));
+        assert!(pool.is_empty());
+
+        let next = pool.allocate();
+        assert_ne!(next.index(), id.index());
+        assert_eq!(pool.len(), 1);
+    }
+}
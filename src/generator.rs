@@ -0,0 +1,171 @@
+use crate::domain::IdDomain;
+use crate::exhaustion::Exhausted;
+use crate::source::{non_zero, IdSource};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::num::NonZeroUsize;
+
+/// A generator with its own counter, for ID spaces scoped to an arena, a document or a test.
+///
+/// Every generator counts up from one independently, so IDs from different generators can share a value. To catch
+/// mixing them up, each [`GeneratedID`] carries the identity of its generator in debug builds, and comparing IDs
+/// from different generators panics. Release builds leave the identity out and compare only the value.
+///
+/// # Example
+/// ```
+/// use runtime_id::IdGenerator;
+///
+/// let nodes = IdGenerator::new();
+/// let a = nodes.next();
+/// let b = nodes.next();
+/// assert_ne!(a, b);
+/// assert_eq!(b.get(), a.get() + 1);
+/// ```
+pub struct IdGenerator {
+    domain: IdDomain,
+    #[cfg(debug_assertions)]
+    brand: crate::RuntimeID,
+}
+
+impl IdGenerator {
+    /// Creates a generator whose first ID is one.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        IdGenerator {
+            domain: IdDomain::starting_at(1),
+            #[cfg(debug_assertions)]
+            brand: crate::RuntimeID::new(),
+        }
+    }
+
+    /// Creates a new ID unique within this generator.
+    ///
+    /// If the generator is exhausted the current [`ExhaustionPolicy`](crate::ExhaustionPolicy) decides what happens.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&self) -> GeneratedID {
+        self.brand(self.domain.next_id())
+    }
+
+    /// Creates a new ID unique within this generator, or returns [`Exhausted`] if every ID has been handed out.
+    #[inline]
+    pub fn try_next(&self) -> Result<GeneratedID, Exhausted> {
+        Ok(self.brand(self.domain.try_next_id()?))
+    }
+
+    #[inline]
+    fn brand(&self, id: usize) -> GeneratedID {
+        GeneratedID {
+            id: non_zero(id),
+            #[cfg(debug_assertions)]
+            generator: self.brand,
+        }
+    }
+}
+
+/// Generators are also sources, for code written against [`IdSource`]. IDs created that way are plain
+/// [`RuntimeID`](crate::RuntimeID)s and don't carry the generator's identity.
+impl IdSource for IdGenerator {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        self.domain.try_next_id().map(non_zero)
+    }
+}
+
+/// An ID created by an [`IdGenerator`], unique among IDs from the same generator.
+#[derive(Clone, Copy)]
+pub struct GeneratedID {
+    id: NonZeroUsize,
+    #[cfg(debug_assertions)]
+    generator: crate::RuntimeID,
+}
+
+impl GeneratedID {
+    /// Returns the ID's value within its generator, starting at one.
+    #[inline]
+    pub fn get(self) -> usize {
+        self.id.get()
+    }
+
+    #[inline]
+    fn check_generator(&self, _other: &Self) {
+        #[cfg(debug_assertions)]
+        assert!(self.generator == _other.generator, "compared GeneratedIDs from different IdGenerators");
+    }
+}
+
+impl fmt::Debug for GeneratedID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tuple = f.debug_tuple("GeneratedID");
+        tuple.field(&self.id);
+        #[cfg(debug_assertions)]
+        tuple.field(&format_args!("generator {}", self.generator));
+        tuple.finish()
+    }
+}
+
+impl PartialEq for GeneratedID {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.check_generator(other);
+        self.id == other.id
+    }
+}
+impl Eq for GeneratedID {}
+
+impl PartialOrd for GeneratedID {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GeneratedID {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.check_generator(other);
+        self.id.cmp(&other.id)
+    }
+}
+
+impl Hash for GeneratedID {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.id.get());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{IdGenerator, RuntimeID};
+
+    #[test]
+    fn independent() {
+        let a = IdGenerator::new();
+        let b = IdGenerator::new();
+
+        assert_eq!(a.next().get(), 1);
+        assert_eq!(a.next().get(), 2);
+        assert_eq!(b.next().get(), 1);
+        assert_eq!(a.try_next().unwrap().get(), 3);
+        assert_eq!(RuntimeID::new_in(&b).id.get(), 2);
+    }
+
+    #[test]
+    fn same_generator() {
+        let generator = IdGenerator::new();
+        let id = generator.next();
+        assert_eq!(id, id);
+        assert!(id < generator.next());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "different IdGenerators")]
+    fn different_generators() {
+        let a = IdGenerator::new().next();
+        let b = IdGenerator::new().next();
+        let _ = a == b;
+    }
+}
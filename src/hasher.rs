@@ -0,0 +1,115 @@
+use core::hash::{BuildHasherDefault, Hasher};
+
+/// 2^64 divided by the golden ratio, the multiplier for Fibonacci hashing.
+const FIBONACCI: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// A fast [`Hasher`] for keys that are already unique integers, such as [`RuntimeID`](crate::RuntimeID).
+///
+/// Integers are mixed with a single Fibonacci multiply, which spreads sequential IDs across both the high and low
+/// bits used by hash tables. This is not resistant to deliberately chosen keys, which IDs handed out by this crate
+/// can't be. Other data is accepted but hashed with a simple byte-at-a-time mix.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuntimeIdHasher(u64);
+
+impl Hasher for RuntimeIdHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ u64::from(byte)).wrapping_mul(FIBONACCI);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.0 = (self.0 ^ value).wrapping_mul(FIBONACCI);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, value: u32) {
+        self.write_u64(u64::from(value));
+    }
+
+    #[inline]
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+/// [`BuildHasher`](core::hash::BuildHasher) for [`RuntimeIdHasher`].
+pub type BuildRuntimeIdHasher = BuildHasherDefault<RuntimeIdHasher>;
+
+/// A `HashMap` keyed by IDs, using [`RuntimeIdHasher`].
+///
+/// # Example
+/// ```
+/// use runtime_id::{RuntimeID, RuntimeIdMap};
+///
+/// let mut names = RuntimeIdMap::default();
+/// let id = RuntimeID::new();
+/// names.insert(id, "db-pool");
+/// assert_eq!(names[&id], "db-pool");
+/// ```
+#[cfg(feature = "std")]
+pub type RuntimeIdMap<V, Tag = ()> = std::collections::HashMap<crate::TaggedRuntimeID<Tag>, V, BuildRuntimeIdHasher>;
+
+/// A `HashSet` of IDs, using [`RuntimeIdHasher`].
+#[cfg(feature = "std")]
+pub type RuntimeIdSet<Tag = ()> = std::collections::HashSet<crate::TaggedRuntimeID<Tag>, BuildRuntimeIdHasher>;
+
+#[cfg(test)]
+mod test {
+    use super::RuntimeIdHasher;
+    use crate::RuntimeID;
+    use core::hash::{Hash, Hasher};
+
+    fn hash<T: Hash>(value: T) -> u64 {
+        let mut hasher = RuntimeIdHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn distinct() {
+        let first = hash(RuntimeID::new());
+        for _ in 0..100000 {
+            assert_ne!(hash(RuntimeID::new()), first);
+        }
+    }
+
+    #[test]
+    fn spreads_high_bits() {
+        // Hash tables take their tag bits from the top of the hash, so sequential IDs must not share them.
+        let mut seen = [false; 128];
+        for value in 1..=1024usize {
+            seen[(hash(value) >> 57) as usize] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn bytes() {
+        assert_ne!(hash("runtime"), hash("id"));
+        assert_ne!(hash([1u8, 2]), hash([2u8, 1]));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn map() {
+        use crate::{RuntimeIdMap, RuntimeIdSet};
+
+        let ids: std::vec::Vec<RuntimeID> = RuntimeID::reserve(1000).collect();
+        let map: RuntimeIdMap<usize> = ids.iter().enumerate().map(|(index, &id)| (id, index)).collect();
+        let set: RuntimeIdSet = ids.iter().copied().collect();
+
+        for (index, id) in ids.iter().enumerate() {
+            assert_eq!(map[id], index);
+            assert!(set.contains(id));
+        }
+        assert!(!set.contains(&RuntimeID::new()));
+    }
+}
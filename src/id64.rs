@@ -0,0 +1,153 @@
+use crate::atomic64::Atomic64;
+use crate::exhaustion::{exhaustion_policy, Exhausted, LIMIT64};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::num::NonZeroU64;
+
+static ID: Counter64 = Counter64::starting_at(1);
+
+struct Counter64 {
+    next: Atomic64,
+}
+
+impl Counter64 {
+    const fn starting_at(first: u64) -> Self {
+        Counter64 { next: Atomic64::new(first) }
+    }
+
+    #[inline]
+    fn try_next_id(&self) -> Result<u64, Exhausted> {
+        let id = self.next.fetch_add(1);
+        if id > LIMIT64 {
+            self.next.store(LIMIT64 + 1);
+            return Err(Exhausted);
+        }
+        Ok(id)
+    }
+}
+
+/// Opaque ID that's unique per 'run' of a program and always 64 bits wide.
+///
+/// [`RuntimeID`](crate::RuntimeID) is as wide as `usize`, so 32-bit targets have a much smaller ID space than 64-bit
+/// ones. RuntimeID64 has the same range everywhere. Targets without native 64-bit atomics fall back to a spinlock,
+/// which is slower but keeps the crate usable in `no_std` environments.
+///
+/// IDs come from their own counter, so a RuntimeID64 and a [`RuntimeID`](crate::RuntimeID) may share a value. They
+/// are ordered by creation with the same guarantees as [`RuntimeID::new`](crate::TaggedRuntimeID#ordering).
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RuntimeID64(pub(crate) NonZeroU64);
+
+impl RuntimeID64 {
+    /// Creates a new unique RuntimeID64.
+    ///
+    /// If every ID has already been handed out the current [`ExhaustionPolicy`](crate::ExhaustionPolicy) decides what
+    /// happens.
+    ///
+    /// # Example
+    /// ```
+    /// let a = runtime_id::RuntimeID64::new();
+    /// let b = runtime_id::RuntimeID64::new();
+    /// assert_ne!(a, b);
+    /// ```
+    #[inline]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        match ID.try_next_id() {
+            Ok(id) => Self::from_counter(id),
+            Err(Exhausted) => Self::from_counter(exhaustion_policy().handle(LIMIT64)),
+        }
+    }
+
+    /// Creates a new unique RuntimeID64, or returns [`Exhausted`] if every ID has already been handed out.
+    #[inline]
+    pub fn try_new() -> Result<Self, Exhausted> {
+        ID.try_next_id().map(Self::from_counter)
+    }
+
+    #[inline]
+    fn from_counter(id: u64) -> Self {
+        match NonZeroU64::new(id) {
+            Some(id) => RuntimeID64(id),
+            None => unreachable!("the RuntimeID64 counter starts at one and never wraps"),
+        }
+    }
+}
+
+impl fmt::Debug for RuntimeID64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RuntimeID64").field(&self.0).finish()
+    }
+}
+
+impl PartialEq for RuntimeID64 {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl Eq for RuntimeID64 {}
+
+impl PartialOrd for RuntimeID64 {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RuntimeID64 {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl Hash for RuntimeID64 {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.0.get());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::Counter64;
+    use crate::exhaustion::LIMIT64;
+    use crate::{Exhausted, RuntimeID64};
+    use std::collections::HashSet;
+    use std::vec::Vec;
+
+    #[test]
+    fn basic() {
+        let a = RuntimeID64::new();
+        let b = RuntimeID64::new();
+        assert_ne!(a, b);
+        assert_eq!(core::mem::size_of::<RuntimeID64>(), 8);
+        assert_eq!(core::mem::size_of::<Option<RuntimeID64>>(), 8);
+    }
+
+    #[test]
+    fn exhaustion() {
+        let counter = Counter64::starting_at(LIMIT64);
+        assert_eq!(counter.try_next_id(), Ok(LIMIT64));
+        assert_eq!(counter.try_next_id(), Err(Exhausted));
+        assert_eq!(counter.try_next_id(), Err(Exhausted));
+    }
+
+    #[test]
+    fn unique_across_threads() {
+        let counter = Counter64::starting_at(1);
+        let ids: Vec<u64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| (0..10000).map(|_| counter.try_next_id().unwrap()).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        assert_eq!(counter.try_next_id(), Ok(80001));
+    }
+}
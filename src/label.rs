@@ -0,0 +1,68 @@
+use crate::{BuildRuntimeIdHasher, TaggedRuntimeID};
+use core::hash::BuildHasherDefault;
+use std::collections::HashMap;
+use std::string::String;
+use std::sync::{PoisonError, RwLock};
+
+static LABELS: RwLock<HashMap<usize, String, BuildRuntimeIdHasher>> =
+    RwLock::new(HashMap::with_hasher(BuildHasherDefault::new()));
+
+pub(crate) fn set(id: usize, label: &str) {
+    LABELS.write().unwrap_or_else(PoisonError::into_inner).insert(id, label.into());
+}
+
+pub(crate) fn clear(id: usize) {
+    LABELS.write().unwrap_or_else(PoisonError::into_inner).remove(&id);
+}
+
+pub(crate) fn get(id: usize) -> Option<String> {
+    LABELS.read().unwrap_or_else(PoisonError::into_inner).get(&id).cloned()
+}
+
+impl<Tag> TaggedRuntimeID<Tag> {
+    /// Returns the label attached with [`set_label`](Self::set_label), if any.
+    pub fn label(&self) -> Option<String> {
+        get(self.id.get())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::RuntimeID;
+    use std::format;
+
+    #[test]
+    fn set_and_clear() {
+        let id = RuntimeID::new();
+        let other = RuntimeID::new();
+        assert_eq!(id.label(), None);
+
+        id.set_label("db-pool");
+        assert_eq!(id.label().as_deref(), Some("db-pool"));
+        assert_eq!(other.label(), None);
+        assert!(format!("{:?}", id).starts_with(&format!("RuntimeID({}, \"db-pool\"", id)));
+
+        id.set_label("cache");
+        assert_eq!(id.label().as_deref(), Some("cache"));
+
+        id.clear_label();
+        assert_eq!(id.label(), None);
+        assert!(!format!("{:?}", id).contains("cache"));
+    }
+
+    #[test]
+    fn concurrent() {
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        let id = RuntimeID::new();
+                        id.set_label(&format!("{}", id));
+                        assert_eq!(id.label(), Some(format!("{}", id)));
+                        id.clear_label();
+                    }
+                });
+            }
+        });
+    }
+}
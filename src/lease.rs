@@ -0,0 +1,227 @@
+//! Blocks of IDs leased from a small server over a Unix domain socket.
+//!
+//! A [`LeaseServer`] owns a counter and hands out consecutive blocks of it to any process on the host that connects.
+//! A [`LeaseClient`] is an [`IdSource`](crate::IdSource) that takes IDs from its current block and asks the server
+//! for a new one whenever the block is used up or its lease has expired, so short-lived processes get unique IDs
+//! without a round trip per ID. The server never hands out the same ID twice; with the `persist` feature it can keep
+//! its counter in a `persist::PersistentCounter` so that holds across restarts too.
+//! Without one a restarted server counts from one again, and clients reject any block that starts before the end of
+//! their last one with [`LeaseError::Reissued`].
+//!
+//! The `runtime_id_server` binary runs a server from the command line.
+//!
+//! # Expiry
+//! Every lease is valid for the server's lease duration, counted by the client from when it sent the request. Once
+//! a lease expires the client abandons whatever is left of the block and leases a new one, so no process sits on a
+//! block for longer than that. The IDs left over are never handed out again.
+//!
+//! # Protocol
+//! Requests and responses are frames: a big-endian `u32` length followed by that many bytes of payload. The first
+//! byte of a payload is its kind, and the rest are big-endian fields.
+//! - A lease request is `1`, then the number of IDs wanted as a `u32`.
+//! - A grant is `1`, then the first ID as a `u64`, the number of IDs as a `u32` and the lease duration in
+//!   milliseconds as a `u64`. The server may grant fewer IDs than requested, but never zero, and never any past
+//!   `i64::MAX`.
+//! - A refusal is `2`, then a UTF-8 message explaining why.
+//!
+//! A connection can carry any number of requests, each answered in order.
+
+pub mod client;
+pub mod server;
+
+pub use client::{LeaseClient, LeaseError};
+pub use server::LeaseServer;
+
+use crate::exhaustion::LIMIT64;
+use std::io::{self, Read, Write};
+use std::string::String;
+use std::vec::Vec;
+
+/// The largest frame either side accepts, far more than any valid message needs.
+const MAX_FRAME: u32 = 1024;
+
+const LEASE: u8 = 1;
+const GRANTED: u8 = 1;
+const REFUSED: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Request {
+    Lease { count: u32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Response {
+    Granted { first: u64, count: u32, duration_ms: u64 },
+    Refused(String),
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_frame(writer: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len()).ok().filter(|&len| len <= MAX_FRAME);
+    let len = len.ok_or_else(|| invalid("frame too large"))?;
+    writer.write_all(&[&len.to_be_bytes()[..], payload].concat())?;
+    writer.flush()
+}
+
+/// Reads the next frame, or returns `None` if the stream ended cleanly before it.
+fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let started = loop {
+        match reader.read(&mut len[..1]) {
+            Ok(read) => break read == 1,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    };
+    if !started {
+        return Ok(None);
+    }
+    reader.read_exact(&mut len[1..])?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME {
+        return Err(invalid("frame too large"));
+    }
+    let mut payload = std::vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// Splits a fixed size field off the front of `bytes`.
+fn field<const N: usize>(bytes: &mut &[u8]) -> io::Result<[u8; N]> {
+    let (field, rest) = bytes.split_first_chunk::<N>().ok_or_else(|| invalid("truncated message"))?;
+    *bytes = rest;
+    Ok(*field)
+}
+
+impl Request {
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Request::Lease { count } => write_frame(writer, &[&[LEASE][..], &count.to_be_bytes()].concat()),
+        }
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let Some(payload) = read_frame(reader)? else { return Ok(None) };
+        let mut bytes = &payload[..];
+        let request = match field::<1>(&mut bytes)? {
+            [LEASE] => Request::Lease { count: u32::from_be_bytes(field(&mut bytes)?) },
+            _ => return Err(invalid("unknown request")),
+        };
+        if !bytes.is_empty() {
+            return Err(invalid("trailing bytes in message"));
+        }
+        Ok(Some(request))
+    }
+}
+
+impl Response {
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Response::Granted { first, count, duration_ms } => write_frame(
+                writer,
+                &[&[GRANTED][..], &first.to_be_bytes(), &count.to_be_bytes(), &duration_ms.to_be_bytes()].concat(),
+            ),
+            Response::Refused(message) => write_frame(writer, &[&[REFUSED], message.as_bytes()].concat()),
+        }
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let payload = read_frame(reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let mut bytes = &payload[..];
+        let response = match field::<1>(&mut bytes)? {
+            [GRANTED] => {
+                let first = u64::from_be_bytes(field(&mut bytes)?);
+                let count = u32::from_be_bytes(field(&mut bytes)?);
+                let duration_ms = u64::from_be_bytes(field(&mut bytes)?);
+                if first == 0 || count == 0 {
+                    return Err(invalid("empty grant"));
+                }
+                if first.checked_add(count.into()).is_none_or(|end| end > LIMIT64 + 1) {
+                    return Err(invalid("grant goes past the last ID"));
+                }
+                Response::Granted { first, count, duration_ms }
+            }
+            [REFUSED] => {
+                let message = core::str::from_utf8(bytes).map_err(|_| invalid("refusal is not UTF-8"))?;
+                bytes = &[];
+                Response::Refused(message.into())
+            }
+            _ => return Err(invalid("unknown response")),
+        };
+        if !bytes.is_empty() {
+            return Err(invalid("trailing bytes in message"));
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Request, Response, MAX_FRAME};
+    use crate::exhaustion::LIMIT64;
+    use std::io;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    fn round_trip_request(request: Request) {
+        let mut bytes = Vec::new();
+        request.write(&mut bytes).unwrap();
+        assert_eq!(Request::read(&mut &bytes[..]).unwrap(), Some(request));
+    }
+
+    fn round_trip_response(response: Response) {
+        let mut bytes = Vec::new();
+        response.write(&mut bytes).unwrap();
+        assert_eq!(Response::read(&mut &bytes[..]).unwrap(), response);
+    }
+
+    #[test]
+    fn round_trip() {
+        round_trip_request(Request::Lease { count: 1024 });
+        round_trip_response(Response::Granted { first: 1, count: u32::MAX, duration_ms: 30000 });
+        round_trip_response(Response::Refused("no IDs left".to_string()));
+    }
+
+    #[test]
+    fn wire_format() {
+        let mut bytes = Vec::new();
+        Request::Lease { count: 258 }.write(&mut bytes).unwrap();
+        assert_eq!(bytes, [0, 0, 0, 5, 1, 0, 0, 1, 2]);
+        assert_eq!(Request::read(&mut &[][..]).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let malformed: [&[u8]; 6] = [
+            &[0, 0, 0, 5, 9, 0, 0, 0, 1],
+            &[0, 0, 0, 4, 1, 0, 0, 1],
+            &[0, 0, 0, 6, 1, 0, 0, 0, 1, 0],
+            &[0, 0, 0, 5, 1, 0, 0],
+            &(MAX_FRAME + 1).to_be_bytes(),
+            &[0, 0],
+        ];
+        for bytes in malformed {
+            let kind = Request::read(&mut &bytes[..]).unwrap_err().kind();
+            assert!(matches!(kind, io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof), "{bytes:?}");
+        }
+
+        let empty_grant = [&[0, 0, 0, 21, 1][..], &[0; 8], &[0, 0, 0, 1], &[0; 8]].concat();
+        assert_eq!(Response::read(&mut &empty_grant[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_grants_past_limit() {
+        let grant = |first: u64, count: u32| {
+            let mut bytes = Vec::new();
+            Response::Granted { first, count, duration_ms: 0 }.write(&mut bytes).unwrap();
+            Response::read(&mut &bytes[..])
+        };
+        assert!(grant(LIMIT64 - 1, 2).is_ok());
+        for (first, count) in [(LIMIT64 - 1, 3), (LIMIT64 + 1, 1), (u64::MAX - 1, 10), (u64::MAX, u32::MAX)] {
+            assert_eq!(grant(first, count).unwrap_err().kind(), io::ErrorKind::InvalidData, "{first} + {count}");
+        }
+    }
+}
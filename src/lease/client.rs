@@ -0,0 +1,389 @@
+//! The client side of [ID leasing](super).
+
+use super::{Request, Response};
+use crate::exhaustion::Exhausted;
+use crate::source::IdSource;
+use core::num::{NonZeroU64, NonZeroUsize};
+use std::io::{self, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::string::String;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a [`LeaseClient`] waits for the server to accept a request or answer it, unless changed with
+/// [`set_timeout`](LeaseClient::set_timeout).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Takes IDs from blocks leased from a [`LeaseServer`](super::LeaseServer), leasing a new block whenever the
+/// current one is used up or expires. See the [module docs](super).
+///
+/// A client can be shared between threads, which then take IDs from the same block. Threads wait for each other
+/// while a block is being leased, for at most the client's [`timeout`](Self::timeout) per attempt.
+#[derive(Debug)]
+pub struct LeaseClient {
+    path: PathBuf,
+    block_size: u32,
+    timeout: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    stream: Option<UnixStream>,
+    /// The leased IDs not yet handed out, `next..end`.
+    next: u64,
+    end: u64,
+    /// When the current lease expires, or `None` if it never does.
+    expires: Option<Instant>,
+}
+
+/// Why a [`LeaseClient`] couldn't lease a new block.
+#[derive(Debug)]
+pub enum LeaseError {
+    /// The server refused the lease, for example because it has run out of IDs.
+    Refused(String),
+    /// The server granted IDs below the end of an earlier lease, which this client may already have handed out.
+    /// This happens when a server without a counter file restarts and counts from one again. Later attempts keep
+    /// being rejected until the server's counter has passed `previous_end`.
+    Reissued {
+        /// The first ID of the rejected grant.
+        first: u64,
+        /// The end of the latest lease this client accepted.
+        previous_end: u64,
+    },
+    /// The server couldn't be reached or broke the protocol.
+    Io(io::Error),
+}
+
+impl core::fmt::Display for LeaseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LeaseError::Refused(message) => write!(f, "RuntimeID lease refused: {message}"),
+            LeaseError::Reissued { first, previous_end } => {
+                write!(f, "RuntimeID lease server reissued IDs from {first}, before the end of the last lease at ")?;
+                write!(f, "{previous_end}")
+            }
+            LeaseError::Io(error) => write!(f, "failed to lease RuntimeIDs: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LeaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LeaseError::Refused(_) | LeaseError::Reissued { .. } => None,
+            LeaseError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl LeaseClient {
+    /// Connects to the server listening on `path`, leasing IDs `block_size` at a time.
+    ///
+    /// No IDs are leased until the first one is needed. If the connection is lost later, the client reconnects to
+    /// the same path the next time it needs a block. Requests time out after [`DEFAULT_TIMEOUT`].
+    ///
+    /// # Panics
+    /// Panics if `block_size` is zero.
+    pub fn connect(path: impl AsRef<Path>, block_size: u32) -> io::Result<Self> {
+        assert!(block_size > 0, "block size must be at least one");
+        let path = path.as_ref().to_path_buf();
+        let stream = open(&path, DEFAULT_TIMEOUT)?;
+        let state = State { stream: Some(stream), next: 0, end: 0, expires: None };
+        Ok(LeaseClient { path, block_size, timeout: DEFAULT_TIMEOUT, state: Mutex::new(state) })
+    }
+
+    /// Sets how long to wait for the server to accept a request or answer it. A server that takes longer is
+    /// reported as [`LeaseError::Io`] with a timed out error, and the connection is replaced on the next attempt.
+    ///
+    /// # Panics
+    /// Panics if `timeout` is zero.
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        assert!(!timeout.is_zero(), "timeout must be longer than zero");
+        self.timeout = timeout;
+        let state = self.state.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match &state.stream {
+            Some(stream) => set_timeouts(stream, timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns how long to wait for the server to accept a request or answer it.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Takes the next ID, leasing a new block first if the current one is used up or has expired.
+    pub fn try_next_u64(&self) -> Result<NonZeroU64, LeaseError> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if state.next == state.end || state.expires.is_some_and(|expires| Instant::now() >= expires) {
+            self.refill(&mut state)?;
+        }
+
+        let id = state.next;
+        state.next += 1;
+        Ok(NonZeroU64::new(id).expect("servers never grant zero"))
+    }
+
+    /// Returns the number of IDs leased at a time.
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Returns the path of the server's socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn refill(&self, state: &mut State) -> Result<(), LeaseError> {
+        let sent = Instant::now();
+        let response = match state.stream.as_ref().map(|stream| self.lease(stream)) {
+            Some(Ok(response)) => response,
+            // The server may have restarted since the last lease, so try once more on a new connection.
+            _ => {
+                state.stream = None;
+                let stream = open(&self.path, self.timeout).map_err(LeaseError::Io)?;
+                let response = self.lease(&stream).map_err(LeaseError::Io)?;
+                state.stream = Some(stream);
+                response
+            }
+        };
+
+        match response {
+            Response::Granted { first, .. } if first < state.end => {
+                Err(LeaseError::Reissued { first, previous_end: state.end })
+            }
+            Response::Granted { first, count, duration_ms } => {
+                state.next = first;
+                state.end = first + u64::from(count);
+                state.expires = sent.checked_add(Duration::from_millis(duration_ms));
+                Ok(())
+            }
+            Response::Refused(message) => Err(LeaseError::Refused(message)),
+        }
+    }
+
+    fn lease(&self, stream: &UnixStream) -> io::Result<Response> {
+        Request::Lease { count: self.block_size }.write(&mut &*stream)?;
+        Response::read(&mut BufReader::new(stream))
+    }
+}
+
+fn open(path: &Path, timeout: Duration) -> io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    set_timeouts(&stream, timeout)?;
+    Ok(stream)
+}
+
+fn set_timeouts(stream: &UnixStream, timeout: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))
+}
+
+/// A block that can't be leased is reported as [`Exhausted`], since the client can't hand out any more IDs.
+impl IdSource for LeaseClient {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        self.try_next_u64().map_err(|_| Exhausted)?.try_into().map_err(|_| Exhausted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::server::test::{bind_nearly_exhausted, spawn, temp_socket};
+    use super::super::{LeaseServer, Request, Response};
+    use super::{LeaseClient, LeaseError};
+    use crate::child;
+    use crate::exhaustion::LIMIT64;
+    use crate::RuntimeID;
+    use std::collections::HashSet;
+    use std::io;
+    use std::io::BufReader;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::process::{Child, Stdio};
+    use std::string::ToString;
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+
+    const COUNTER_ENV: &str = "RUNTIME_ID_LEASE_COUNTER";
+
+    fn next(client: &LeaseClient) -> u64 {
+        client.try_next_u64().unwrap().get()
+    }
+
+    #[test]
+    fn refills() {
+        let server = spawn(LeaseServer::bind(temp_socket(), Duration::from_secs(60)).unwrap());
+        let first = LeaseClient::connect(server.path(), 3).unwrap();
+        let second = LeaseClient::connect(server.path(), 3).unwrap();
+
+        assert_eq!(next(&first), 1);
+        assert_eq!(next(&second), 4);
+        assert_eq!(next(&first), 2);
+        assert_eq!(next(&first), 3);
+        assert_eq!(next(&first), 7);
+        assert_eq!(RuntimeID::new_in(&second).to_string(), "5");
+        assert_eq!(server.active_leases(), 3);
+    }
+
+    #[test]
+    fn abandons_expired_lease() {
+        let server = spawn(LeaseServer::bind(temp_socket(), Duration::from_millis(50)).unwrap());
+        let client = LeaseClient::connect(server.path(), 100).unwrap();
+        assert_eq!(next(&client), 1);
+        assert_eq!(next(&client), 2);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(next(&client), 101);
+    }
+
+    #[test]
+    fn unique_across_clients() {
+        let server = spawn(LeaseServer::bind(temp_socket(), Duration::from_secs(60)).unwrap());
+        let shared = LeaseClient::connect(server.path(), 7).unwrap();
+
+        let ids: Vec<u64> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|thread| {
+                    let shared = &shared;
+                    let path = server.path();
+                    scope.spawn(move || {
+                        let own = LeaseClient::connect(path, 5 + thread).unwrap();
+                        (0..1000).flat_map(|_| [next(&own), next(shared)]).collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+        });
+        assert_eq!(ids.len(), 16000);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    }
+
+    #[test]
+    fn reports_refusal() {
+        let server = spawn(bind_nearly_exhausted(&temp_socket()));
+        let client = LeaseClient::connect(server.path(), 2).unwrap();
+        assert_eq!(next(&client), LIMIT64 - 2);
+        assert_eq!(next(&client), LIMIT64 - 1);
+        assert_eq!(next(&client), LIMIT64);
+        assert!(matches!(client.try_next_u64(), Err(LeaseError::Refused(_))));
+        assert!(RuntimeID::try_new_in(&client).is_err());
+    }
+
+    /// Accepts two connections on `path`, one after the other, since a client retries a failed lease once on a new
+    /// connection. Each connection's first request is answered with `response`, or never answered if it's `None`.
+    fn fake_server(path: &Path, response: Option<Response>) -> std::thread::JoinHandle<()> {
+        let listener = UnixListener::bind(path).unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                assert!(matches!(Request::read(&mut reader), Ok(Some(Request::Lease { .. }))));
+                if let Some(response) = &response {
+                    response.write(&mut &stream).unwrap();
+                }
+                // Wait for the client to hang up.
+                while let Ok(Some(_)) = Request::read(&mut reader) {}
+            }
+        })
+    }
+
+    #[test]
+    fn rejects_grants_past_limit() {
+        let socket = temp_socket();
+        let server = fake_server(&socket, Some(Response::Granted { first: u64::MAX - 1, count: 10, duration_ms: 0 }));
+        let client = LeaseClient::connect(&socket, 10).unwrap();
+        let error = client.try_next_u64().unwrap_err();
+        assert!(matches!(&error, LeaseError::Io(error) if error.kind() == io::ErrorKind::InvalidData), "{error:?}");
+        server.join().unwrap();
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[test]
+    fn times_out() {
+        let socket = temp_socket();
+        let server = fake_server(&socket, None);
+        let mut client = LeaseClient::connect(&socket, 10).unwrap();
+        client.set_timeout(Duration::from_millis(50)).unwrap();
+        assert_eq!(client.timeout(), Duration::from_millis(50));
+
+        let started = Instant::now();
+        let error = client.try_next_u64().unwrap_err();
+        assert!(started.elapsed() < super::DEFAULT_TIMEOUT, "{error:?}");
+        let timed_out = |error: &io::Error| matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut);
+        assert!(matches!(&error, LeaseError::Io(error) if timed_out(error)), "{error:?}");
+        server.join().unwrap();
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    /// Runs a server in a child process until it's killed, keeping its counter in a file if one is given.
+    fn spawn_process(socket: &Path, counter: Option<&Path>) -> Child {
+        let mut command = child::command("lease::client::test::survives_server_restart", socket);
+        command.stdout(Stdio::null());
+        if let Some(counter) = counter {
+            command.env(COUNTER_ENV, counter);
+        }
+        let child = command.spawn().unwrap();
+
+        let started = Instant::now();
+        while UnixStream::connect(socket).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "server didn't start");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        child
+    }
+
+    #[test]
+    fn survives_server_restart() {
+        if let Some(socket) = child::arg() {
+            let server = match std::env::var_os(COUNTER_ENV) {
+                #[cfg(feature = "persist")]
+                Some(counter) => {
+                    let counter = crate::persist::PersistentCounter::open(counter, 10).unwrap();
+                    LeaseServer::bind_persistent(socket, Duration::from_secs(60), counter).unwrap()
+                }
+                _ => LeaseServer::bind(socket, Duration::from_secs(60)).unwrap(),
+            };
+            server.run().unwrap();
+            return;
+        }
+
+        let socket = temp_socket();
+        let mut server = spawn_process(&socket, None);
+        let client = LeaseClient::connect(&socket, 4).unwrap();
+        assert_eq!(next(&client), 1);
+        server.kill().unwrap();
+        server.wait().unwrap();
+
+        // The rest of the block is still usable, and the next block comes from the restarted server.
+        assert_eq!((0..3).map(|_| next(&client)).collect::<Vec<_>>(), [2, 3, 4]);
+        assert!(matches!(client.try_next_u64(), Err(LeaseError::Io(_))));
+        let mut server = spawn_process(&socket, None);
+        let reissued = client.try_next_u64();
+        assert!(matches!(reissued, Err(LeaseError::Reissued { first: 1, previous_end: 5 })), "{reissued:?}");
+        assert_eq!(next(&client), 5, "once the server's counter has caught up, leases are accepted again");
+        server.kill().unwrap();
+        server.wait().unwrap();
+
+        #[cfg(feature = "persist")]
+        {
+            let counter = socket.with_extension("hwm");
+            let mut server = spawn_process(&socket, Some(&counter));
+            let client = LeaseClient::connect(&socket, 4).unwrap();
+            assert_eq!(next(&client), 1);
+            server.kill().unwrap();
+            server.wait().unwrap();
+
+            let mut server = spawn_process(&socket, Some(&counter));
+            assert_eq!((0..3).map(|_| next(&client)).collect::<Vec<_>>(), [2, 3, 4]);
+            assert_eq!(next(&client), 11, "the restarted server skips the rest of the persisted block");
+            server.kill().unwrap();
+            server.wait().unwrap();
+            for suffix in ["", ".lock"] {
+                std::fs::remove_file(std::format!("{}{suffix}", counter.display())).unwrap();
+            }
+        }
+        let _ = std::fs::remove_file(&socket);
+    }
+}
@@ -0,0 +1,293 @@
+//! The server side of [ID leasing](super).
+
+use super::{Request, Response};
+use crate::exhaustion::LIMIT64;
+#[cfg(feature = "persist")]
+use crate::persist::PersistentCounter;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufReader};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::string::{String, ToString};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The most IDs granted in one lease, whatever the client asks for.
+pub const MAX_LEASE: u32 = 1 << 20;
+
+/// Hands out blocks of IDs to [`LeaseClient`](super::LeaseClient)s over a Unix domain socket. See the
+/// [module docs](super).
+#[derive(Debug)]
+pub struct LeaseServer {
+    listener: UnixListener,
+    path: PathBuf,
+    lease_duration: Duration,
+    counter: Counter,
+    /// When each lease that may still be live expires, oldest first.
+    leases: Mutex<VecDeque<Instant>>,
+}
+
+#[derive(Debug)]
+enum Counter {
+    Memory(Mutex<u64>),
+    #[cfg(feature = "persist")]
+    Persistent(PersistentCounter),
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Counter {
+    /// Takes up to `count` consecutive IDs, returning the first and how many were taken.
+    fn take(&self, count: u32) -> Result<(u64, u32), String> {
+        match self {
+            Counter::Memory(next) => {
+                let mut next = lock(next);
+                let left = LIMIT64 + 1 - *next;
+                if left == 0 {
+                    return Err(crate::Exhausted.to_string());
+                }
+                let count = count.min(left.try_into().unwrap_or(u32::MAX));
+                let first = *next;
+                *next += u64::from(count);
+                Ok((first, count))
+            }
+            #[cfg(feature = "persist")]
+            Counter::Persistent(counter) => match counter.try_reserve_u64(count.into()) {
+                Ok(first) => Ok((first.get(), count)),
+                Err(error) => Err(error.to_string()),
+            },
+        }
+    }
+}
+
+impl LeaseServer {
+    /// Listens on `path` with a counter starting at one, granting leases valid for `lease_duration`.
+    ///
+    /// The counter only lives as long as the server, so a restarted server hands out the same IDs again. Use
+    /// `bind_persistent`, with the `persist` feature, if IDs must stay unique across restarts.
+    ///
+    /// A socket left at `path` by a server that's no longer running is replaced. Fails with
+    /// [`io::ErrorKind::AddrInUse`] if a server is still listening there.
+    pub fn bind(path: impl AsRef<Path>, lease_duration: Duration) -> io::Result<Self> {
+        Self::bind_with(path.as_ref(), lease_duration, Counter::Memory(Mutex::new(1)))
+    }
+
+    /// Listens on `path` like [`bind`](Self::bind), handing out IDs from `counter` so they are never reissued,
+    /// even after the server restarts or crashes.
+    #[cfg(feature = "persist")]
+    pub fn bind_persistent(
+        path: impl AsRef<Path>,
+        lease_duration: Duration,
+        counter: PersistentCounter,
+    ) -> io::Result<Self> {
+        Self::bind_with(path.as_ref(), lease_duration, Counter::Persistent(counter))
+    }
+
+    fn bind_with(path: &Path, lease_duration: Duration, counter: Counter) -> io::Result<Self> {
+        let listener = match UnixListener::bind(path) {
+            Err(error) if error.kind() == io::ErrorKind::AddrInUse => {
+                match UnixStream::connect(path) {
+                    Err(stale) if stale.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path)?,
+                    _ => return Err(error),
+                }
+                UnixListener::bind(path)?
+            }
+            result => result?,
+        };
+        Ok(LeaseServer {
+            listener,
+            path: path.to_path_buf(),
+            lease_duration,
+            counter,
+            leases: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Returns the path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns how long each lease is valid.
+    pub fn lease_duration(&self) -> Duration {
+        self.lease_duration
+    }
+
+    /// Returns the number of leases granted that haven't expired yet.
+    pub fn active_leases(&self) -> usize {
+        let mut leases = lock(&self.leases);
+        prune(&mut leases);
+        leases.len()
+    }
+
+    /// Serves clients until accepting a connection fails, with a thread for each connection.
+    pub fn run(&self) -> io::Result<()> {
+        std::thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = stream?;
+                // A client that breaks the protocol only loses its own connection.
+                scope.spawn(move || self.serve(stream));
+            }
+            Ok(())
+        })
+    }
+
+    fn serve(&self, stream: UnixStream) -> io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+        while let Some(request) = Request::read(&mut reader)? {
+            let response = match request {
+                Request::Lease { count } => self.grant(count),
+            };
+            response.write(&mut &stream)?;
+        }
+        Ok(())
+    }
+
+    fn grant(&self, count: u32) -> Response {
+        if count == 0 {
+            return Response::Refused("a lease must contain at least one ID".into());
+        }
+        match self.counter.take(count.min(MAX_LEASE)) {
+            Ok((first, count)) => {
+                let expires = Instant::now().checked_add(self.lease_duration);
+                let mut leases = lock(&self.leases);
+                prune(&mut leases);
+                leases.extend(expires);
+                let duration_ms = self.lease_duration.as_millis().try_into().unwrap_or(u64::MAX);
+                Response::Granted { first, count, duration_ms }
+            }
+            Err(message) => Response::Refused(message),
+        }
+    }
+}
+
+/// Forgets the leases that have expired, which are always at the front.
+fn prune(leases: &mut VecDeque<Instant>) {
+    let now = Instant::now();
+    while leases.front().is_some_and(|&expires| expires <= now) {
+        leases.pop_front();
+    }
+}
+
+impl Drop for LeaseServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::super::{Request, Response};
+    use super::{Counter, LeaseServer, MAX_LEASE};
+    use crate::exhaustion::LIMIT64;
+    use std::io::{self, BufReader};
+    use std::ops::Deref;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    pub(crate) fn temp_socket() -> PathBuf {
+        crate::child::temp_path("lease").with_extension("sock")
+    }
+
+    /// A server running on a background thread for the rest of the test process. Dropping it removes the socket
+    /// so no new clients can connect.
+    pub(crate) struct Running(Arc<LeaseServer>);
+
+    impl Deref for Running {
+        type Target = LeaseServer;
+
+        fn deref(&self) -> &LeaseServer {
+            &self.0
+        }
+    }
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(self.path());
+        }
+    }
+
+    /// Binds a server with only the last three IDs left.
+    pub(crate) fn bind_nearly_exhausted(path: &Path) -> LeaseServer {
+        LeaseServer::bind_with(path, Duration::from_secs(60), Counter::Memory(Mutex::new(LIMIT64 - 2))).unwrap()
+    }
+
+    pub(crate) fn spawn(server: LeaseServer) -> Running {
+        let server = Arc::new(server);
+        let running = server.clone();
+        std::thread::spawn(move || running.run());
+        Running(server)
+    }
+
+    fn lease(stream: &UnixStream, count: u32) -> Response {
+        Request::Lease { count }.write(&mut &*stream).unwrap();
+        Response::read(&mut BufReader::new(stream)).unwrap()
+    }
+
+    #[test]
+    fn grants_consecutive_blocks() {
+        let server = spawn(LeaseServer::bind(temp_socket(), Duration::from_secs(60)).unwrap());
+        let stream = UnixStream::connect(server.path()).unwrap();
+        let other = UnixStream::connect(server.path()).unwrap();
+
+        assert_eq!(lease(&stream, 10), Response::Granted { first: 1, count: 10, duration_ms: 60000 });
+        assert_eq!(lease(&other, 5), Response::Granted { first: 11, count: 5, duration_ms: 60000 });
+        assert_eq!(lease(&stream, u32::MAX), Response::Granted { first: 16, count: MAX_LEASE, duration_ms: 60000 });
+        assert!(matches!(lease(&stream, 0), Response::Refused(_)));
+        assert_eq!(server.active_leases(), 3);
+    }
+
+    #[test]
+    fn leases_expire() {
+        let server = spawn(LeaseServer::bind(temp_socket(), Duration::from_millis(50)).unwrap());
+        let stream = UnixStream::connect(server.path()).unwrap();
+        lease(&stream, 1);
+        lease(&stream, 1);
+        assert_eq!(server.active_leases(), 2);
+        std::thread::sleep(Duration::from_millis(100));
+        lease(&stream, 1);
+        assert_eq!(server.leases.lock().unwrap().len(), 1, "granting forgets expired leases");
+        assert_eq!(server.active_leases(), 1);
+    }
+
+    #[test]
+    fn exhaustion() {
+        let server = spawn(bind_nearly_exhausted(&temp_socket()));
+        let stream = UnixStream::connect(server.path()).unwrap();
+
+        assert_eq!(lease(&stream, 10), Response::Granted { first: LIMIT64 - 2, count: 3, duration_ms: 60000 });
+        assert!(matches!(lease(&stream, 10), Response::Refused(_)));
+    }
+
+    #[test]
+    fn drops_broken_clients() {
+        let server = spawn(LeaseServer::bind(temp_socket(), Duration::from_secs(60)).unwrap());
+        let stream = UnixStream::connect(server.path()).unwrap();
+        io::Write::write_all(&mut &stream, &[0, 0, 0, 1, 9]).unwrap();
+        assert_eq!(Response::read(&mut &stream).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let stream = UnixStream::connect(server.path()).unwrap();
+        assert_eq!(lease(&stream, 1), Response::Granted { first: 1, count: 1, duration_ms: 60000 });
+    }
+
+    #[test]
+    fn replaces_stale_socket() {
+        let path = temp_socket();
+        let server = LeaseServer::bind(&path, Duration::from_secs(60)).unwrap();
+        let error = LeaseServer::bind(&path, Duration::from_secs(60)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+
+        drop(server);
+
+        // A crashed server leaves its socket file behind with nothing listening.
+        drop(UnixListener::bind(&path).unwrap());
+        let server = LeaseServer::bind(&path, Duration::from_secs(60)).unwrap();
+        drop(server);
+        assert!(!path.exists());
+    }
+}
@@ -2,22 +2,255 @@
 
 //! RuntimeID provides lightweight unique identifers per 'run' of a program.
 //!
-//! Internally this is just a usize that counts up from zero using atomic instructions. This makes RuntimeIDs
-//! extremely cheap to create and compare with the downside that they cannot be serialized.
+//! Internally this is just a usize that counts up from one using atomic instructions. This makes RuntimeIDs
+//! extremely cheap to create and compare with the downside that they cannot be serialized, except with the `serde`
+//! feature which ties serialized IDs to the run that created them. Because an ID is never zero, `Option<RuntimeID>`
+//! is the same size as `RuntimeID`.
+//!
+//! A counter never wraps around. Once all `isize::MAX` IDs have been handed out, [`RuntimeID::try_new`] returns
+//! [`Exhausted`] and [`RuntimeID::new`] follows the process wide [`ExhaustionPolicy`].
+//!
+//! IDs can optionally carry a tag type with [`TaggedRuntimeID`], which keeps IDs meant for different things from
+//! being mixed up at compile time. [`RuntimeID`] is the untagged form.
+//!
+//! Subsystems that want their own dense sequence of IDs can declare a separate ID type with
+//! [`define_runtime_id!`], which counts independently of [`RuntimeID`].
+//!
+//! IDs shared between threads can be loaded, swapped and compared-and-exchanged without a lock through
+//! [`AtomicRuntimeID`] and [`AtomicOptionRuntimeID`].
+//!
+//! A [`OnceRuntimeID`] can be created in a `const` context and only takes an ID the first time it's asked for, which
+//! suits statics and struct fields that rarely need one.
+//!
+//! Many IDs can be created with a single atomic operation through [`RuntimeID::reserve`], which returns them as an
+//! [`IdRange`].
+//!
+//! With the `std` feature, `OwnedRuntimeID` gives an ID a single owner and tracks it in a registry of live IDs
+//! until it's dropped, which helps find leaks.
+//!
+//! Code that creates IDs can take an [`IdSource`] and call [`RuntimeID::new_in`] instead of [`RuntimeID::new`], so
+//! tests can inject a deterministic [`SequenceSource`].
+//!
+//! An [`IdGenerator`] is a counter held as a plain value, for ID spaces scoped to an arena or document. Its IDs
+//! remember which generator created them in debug builds, so mixing up generators is caught early.
+//!
+//! The `testing` feature adds `testing::scoped`, which gives IDs created inside it a deterministic sequence for
+//! snapshot tests.
+//!
+//! For IDs that must stay unique across several processes, the [`snowflake`] module packs a node number into each
+//! ID.
+//!
+//! Processes on the same host can share a single counter through a memory-mapped file with the `shared` module.
+//!
+//! Short-lived processes on one host can lease blocks of IDs from a small server over a Unix domain socket with the
+//! `lease` module.
+//!
+//! IDs that must never repeat across restarts can come from a `persist::PersistentCounter`, which leases blocks of
+//! IDs by durably storing a high-water mark and hands them out from memory.
+//!
+//! IDs can be written and parsed as decimal with `Display` and `FromStr`, or in more compact encodings from the
+//! [`encode`] module.
+//!
+//! IDs are ordered by when they were created; see [`TaggedRuntimeID`] for exactly what that guarantees.
+//!
+//! [`RuntimeID64`] is always 64 bits wide, for targets where `usize` doesn't leave enough room.
+//!
+//! Long running programs that need to reuse IDs can allocate `GenerationalID`s from an `IdPool`, which
+//! recycles released indices.
+//!
+//! IDs are already unique integers, so maps keyed by them don't need a general purpose hash function.
+//! [`RuntimeIdHasher`] mixes them with a single multiply, and `RuntimeIdMap` and `RuntimeIdSet` use it by default.
+//!
+//! # Features
+//! - `alloc`: enables `IdPool` and `GenerationalID`.
+//! - `std`: implies `alloc` and enables `OwnedRuntimeID`, `RuntimeIdMap`, `RuntimeIdSet` and
+//!   `RuntimeID::new_local`, which hands out IDs from per-thread blocks to avoid contention.
+//! - `serde`: implies `std` and implements `Serialize` and `Deserialize` for IDs, refusing IDs from other runs.
+//! - `labels`: implies `std` and keeps the labels set with [`RuntimeID::set_label`] so they show up in `Debug`
+//!   output. Without it, setting a label does nothing.
+//! - `track-origin`: implies `std` and, in debug builds, records where each ID was created, available from
+//!   `RuntimeID::origin` and shown in `Debug` output. Origins of copyable IDs are kept for the rest of the run, so
+//!   this is meant for hunting leaks rather than for production. Release builds, and builds without the feature,
+//!   don't pay for tracking at all.
+//! - `shm`: implies `std` and enables the `shared` module on Unix targets with 64-bit atomics.
+//! - `persist`: implies `std` and enables the `persist` module.
+//! - `lease`: implies `std` and enables the `lease` module and the `runtime_id_server` binary on Unix targets.
+//! - `fork-safe`: implies `std` and, on Unix targets, gives each process created with `fork` a block of the
+//!   parent's IDs to create its own from, along with a new `run_token`, so parent and child never take the same
+//!   [`RuntimeID`] from the counter. Ranges reserved before the fork are still shared. On 32-bit targets every fork
+//!   permanently takes a sixty-fourth of the parent's remaining IDs, so a process that forks thousands of times
+//!   will run out.
+//! - `testing`: implies `std` and enables the `testing` module.
+//! - `atomic64-fallback`: forces the lock-based counter behind [`RuntimeID64`] even where 64-bit atomics exist.
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::cmp::Ordering;
+use core::fmt;
 use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::num::NonZeroUsize;
+use core::sync::atomic;
+
+mod atomic_id;
+mod atomic64;
+#[cfg(all(test, feature = "std"))]
+mod child;
+mod domain;
+pub mod encode;
+mod exhaustion;
+#[cfg(all(feature = "fork-safe", unix))]
+mod fork;
+#[cfg(feature = "alloc")]
+mod generational;
+mod generator;
+mod hasher;
+mod id64;
+#[cfg(feature = "labels")]
+mod label;
+#[cfg(all(feature = "lease", unix))]
+pub mod lease;
+#[cfg(feature = "track-origin")]
+mod origin;
+#[cfg(feature = "std")]
+mod local;
+mod once;
+#[cfg(feature = "std")]
+mod owned;
+#[cfg(feature = "persist")]
+pub mod persist;
+mod range;
+#[cfg(feature = "std")]
+mod run;
+pub mod snowflake;
+mod source;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(all(feature = "shm", unix, target_has_atomic = "64"))]
+pub mod shared;
+
+pub use atomic_id::{AtomicOptionRuntimeID, AtomicRuntimeID};
+pub use domain::IdDomain;
+pub use encode::ParseIdError;
+pub use exhaustion::{exhaustion_policy, set_exhaustion_policy, Exhausted, ExhaustionPolicy};
+use exhaustion::LIMIT;
+#[cfg(feature = "alloc")]
+pub use generational::{GenerationalID, IdPool};
+pub use generator::{GeneratedID, IdGenerator};
+pub use hasher::{BuildRuntimeIdHasher, RuntimeIdHasher};
+#[cfg(feature = "std")]
+pub use hasher::{RuntimeIdMap, RuntimeIdSet};
+pub use id64::RuntimeID64;
+pub use once::OnceRuntimeID;
+#[cfg(feature = "track-origin")]
+pub use origin::Origin;
+#[cfg(feature = "std")]
+pub use local::{local_block_size, set_local_block_size, DEFAULT_LOCAL_BLOCK_SIZE};
+#[cfg(feature = "std")]
+pub use owned::OwnedRuntimeID;
+pub use range::IdRange;
+#[cfg(feature = "std")]
+pub use run::run_token;
+#[cfg(feature = "std")]
+pub use source::LocalSource;
+pub use source::{GlobalSource, IdSource, SequenceSource};
+
+static ID: IdDomain = IdDomain::starting_at(1);
 
-static ID: AtomicUsize = AtomicUsize::new(0);
+/// Takes the next ID from the global counter, or from the current thread's test scope if there is one.
+#[inline]
+fn take(ordering: atomic::Ordering) -> Result<usize, Exhausted> {
+    #[cfg(feature = "testing")]
+    if let Some(id) = testing::try_take(1) {
+        return id;
+    }
+    let id = ID.try_take(ordering)?;
+    #[cfg(all(feature = "fork-safe", unix))]
+    fork::check(id, 1)?;
+    Ok(id)
+}
+
+/// Reserves `count` IDs from the global counter, or from the current thread's test scope if there is one.
+#[inline]
+fn reserve(count: usize) -> Result<usize, Exhausted> {
+    #[cfg(feature = "testing")]
+    if let Some(id) = testing::try_take(count) {
+        return id;
+    }
+    let first = ID.try_reserve(count)?;
+    #[cfg(all(feature = "fork-safe", unix))]
+    fork::check(first, count)?;
+    Ok(first)
+}
+
+/// Returns true if `id` has been created by the global counter, or by the current thread's test scope if there is
+/// one.
+#[inline]
+fn has_issued(id: usize) -> bool {
+    #[cfg(feature = "testing")]
+    if let Some(issued) = testing::has_issued(id) {
+        return issued;
+    }
+    #[cfg(all(feature = "fork-safe", unix))]
+    if fork::check(id, 1).is_err() {
+        return false;
+    }
+    ID.has_issued(id)
+}
 
 /// Opaque ID that's unique per 'run' of a program.
+pub type RuntimeID = TaggedRuntimeID<()>;
+
+/// Opaque ID that's unique per 'run' of a program, tagged with a marker type.
+///
+/// IDs with different tags are distinct types but share the same counter, so an ID is unique across all tags.
+/// Converting between tags is explicit through [`TaggedRuntimeID::retag`].
+///
+/// # Layout
+/// A TaggedRuntimeID is `#[repr(C)]` around a single non-zero `usize`, so it has the size and alignment of `usize`
+/// and `Option<TaggedRuntimeID<Tag>>` is guaranteed to be the same size as `TaggedRuntimeID<Tag>`.
+///
+/// # Ordering
+/// IDs compare by the order in which [`new`](Self::new) took them from the counter. Every call is a read-modify-write
+/// of the same atomic, and even with `Relaxed` ordering those form a single total order that is consistent with
+/// happens-before. So:
+/// - Within a thread, an ID created later in program order is always greater.
+/// - Across threads, if creating `a` happens-before creating `b`, for example because `a` was sent over a channel
+///   or a lock was handed over in between, then `a < b`.
+///
+/// The reverse doesn't hold for [`new`](Self::new): seeing `a < b` says nothing about what the thread that created
+/// `b` can observe of the thread that created `a`. IDs from [`new_ordered`](Self::new_ordered) add that guarantee.
+///
+/// IDs from [`reserve`](Self::reserve) and `new_local` are taken from the counter ahead of time,
+/// so their order reflects when their block was reserved, not when they were handed out.
+///
+/// # Example
+/// ```compile_fail
+/// use runtime_id::TaggedRuntimeID;
+///
+/// struct Connection;
+/// struct Session;
+///
+/// fn close_session(_id: TaggedRuntimeID<Session>) {}
+///
+/// close_session(TaggedRuntimeID::<Connection>::new());
+/// ```
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
-pub struct RuntimeID(usize);
+pub struct TaggedRuntimeID<Tag> {
+    id: NonZeroUsize,
+    tag: PhantomData<fn() -> Tag>,
+}
 
-impl RuntimeID {
+impl<Tag> TaggedRuntimeID<Tag> {
     /// Creates a new unique RuntimeID.
     ///
+    /// If every ID has already been handed out the current [`ExhaustionPolicy`] decides what happens.
+    ///
     /// # Example
     /// ```
     /// let a = runtime_id::RuntimeID::new();
@@ -25,31 +258,184 @@ impl RuntimeID {
     /// assert_ne!(a, b);
     /// ```
     #[inline]
+    #[allow(clippy::new_without_default)]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
     pub fn new() -> Self {
-        RuntimeID(ID.fetch_add(1, Ordering::Relaxed))
+        match take(atomic::Ordering::Relaxed) {
+            Ok(id) => Self::from_counter(id).track(),
+            Err(Exhausted) => Self::from_counter(exhaustion_policy().handle(LIMIT)).track(),
+        }
+    }
+
+    /// Creates a new unique RuntimeID that also synchronizes with other IDs from `new_ordered`.
+    ///
+    /// In addition to the guarantees of [`new`](Self::new), the counter is incremented with acquire-release
+    /// ordering. If `a` and `b` both come from `new_ordered` and `a < b`, everything the creating thread did before
+    /// creating `a` happens-before everything the other thread does after creating `b`. Together this gives a total
+    /// order over these IDs that is consistent with happens-before in both directions.
+    ///
+    /// This costs a stronger memory fence on weakly ordered architectures.
+    ///
+    /// # Example
+    /// ```
+    /// let a = runtime_id::RuntimeID::new_ordered();
+    /// let b = runtime_id::RuntimeID::new_ordered();
+    /// assert!(a < b);
+    /// ```
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn new_ordered() -> Self {
+        match take(atomic::Ordering::AcqRel) {
+            Ok(id) => Self::from_counter(id).track(),
+            Err(Exhausted) => Self::from_counter(exhaustion_policy().handle(LIMIT)).track(),
+        }
+    }
+
+    /// Creates a new unique RuntimeID, or returns [`Exhausted`] if every ID has already been handed out.
+    ///
+    /// # Example
+    /// ```
+    /// let id = runtime_id::RuntimeID::try_new().expect("out of IDs");
+    /// ```
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn try_new() -> Result<Self, Exhausted> {
+        Ok(Self::from_counter(take(atomic::Ordering::Relaxed)?).track())
+    }
+
+    /// Converts this ID into an ID with a different tag.
+    ///
+    /// The underlying value is unchanged, so the result compares equal to any other ID retagged from the same value.
+    ///
+    /// # Example
+    /// ```
+    /// use runtime_id::{RuntimeID, TaggedRuntimeID};
+    ///
+    /// struct Session;
+    ///
+    /// let id = RuntimeID::new();
+    /// let session: TaggedRuntimeID<Session> = id.retag();
+    /// assert_eq!(session.retag::<()>(), id);
+    /// ```
+    #[inline]
+    pub fn retag<Other>(self) -> TaggedRuntimeID<Other> {
+        TaggedRuntimeID::from_raw(self.id)
+    }
+
+    /// Attaches a human readable label to this ID, replacing any previous one, to be shown by its `Debug` output.
+    ///
+    /// Labels are kept in a global registry until removed with [`clear_label`](Self::clear_label). Without the
+    /// `labels` feature this does nothing and compiles away.
+    ///
+    /// # Example
+    /// ```
+    /// let id = runtime_id::RuntimeID::new();
+    /// id.set_label("db-pool");
+    /// # #[cfg(feature = "labels")]
+    /// assert!(format!("{:?}", id).contains("\"db-pool\""));
+    /// id.clear_label();
+    /// ```
+    #[inline]
+    pub fn set_label(&self, label: &str) {
+        #[cfg(feature = "labels")]
+        label::set(self.id.get(), label);
+        #[cfg(not(feature = "labels"))]
+        let _ = label;
+    }
+
+    /// Removes the label attached with [`set_label`](Self::set_label), freeing its memory.
+    #[inline]
+    pub fn clear_label(&self) {
+        #[cfg(feature = "labels")]
+        label::clear(self.id.get());
+    }
+
+    /// Records where a freshly created ID came from, if the `track-origin` feature is enabled in a debug build.
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    fn track(self) -> Self {
+        #[cfg(all(feature = "track-origin", debug_assertions))]
+        origin::record(self.id.get(), core::panic::Location::caller());
+        self
+    }
+
+    #[inline]
+    fn from_counter(id: usize) -> Self {
+        match NonZeroUsize::new(id) {
+            Some(id) => Self::from_raw(id),
+            None => unreachable!("the RuntimeID counter starts at one and never wraps"),
+        }
+    }
+
+    #[inline]
+    const fn from_raw(id: NonZeroUsize) -> Self {
+        TaggedRuntimeID { id, tag: PhantomData }
+    }
+}
+
+impl<Tag> Clone for TaggedRuntimeID<Tag> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<Tag> Copy for TaggedRuntimeID<Tag> {}
+
+impl<Tag> fmt::Debug for TaggedRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tuple = f.debug_tuple("RuntimeID");
+        tuple.field(&self.id);
+        #[cfg(feature = "labels")]
+        if let Some(label) = self.label() {
+            tuple.field(&label);
+        }
+        #[cfg(feature = "track-origin")]
+        if let Some(origin) = self.origin() {
+            tuple.field(&format_args!("created at {}", origin.location()));
+        }
+        tuple.finish()
     }
 }
 
-impl PartialEq for RuntimeID {
+impl<Tag> PartialEq for TaggedRuntimeID<Tag> {
     #[inline]
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+        self.id == other.id
     }
 }
-impl Eq for RuntimeID {}
+impl<Tag> Eq for TaggedRuntimeID<Tag> {}
 
-impl Hash for RuntimeID {
+impl<Tag> PartialOrd for TaggedRuntimeID<Tag> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Tag> Ord for TaggedRuntimeID<Tag> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<Tag> Hash for TaggedRuntimeID<Tag> {
     #[inline]
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write(&self.0.to_ne_bytes());
+        state.write_usize(self.id.get());
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::RuntimeID;
+    extern crate std;
+
+    use crate::{RuntimeID, TaggedRuntimeID};
     use ahash::AHasher;
     use core::hash::{Hash, Hasher};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::vec::Vec;
 
     #[test]
     fn basic() {
@@ -68,7 +454,7 @@ mod test {
         RuntimeID::new().hash(&mut hasher);
         let test_hash = hasher.finish();
 
-        const ITTERATIONS: usize = 100000; 
+        const ITTERATIONS: usize = 100000;
         for _ in 0..ITTERATIONS {
             let mut hasher = AHasher::default();
             RuntimeID::new().hash(&mut hasher);
@@ -76,4 +462,110 @@ mod test {
             assert_ne!(hash, test_hash);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn tagged() {
+        struct Connection;
+        struct Session;
+
+        let connection = TaggedRuntimeID::<Connection>::new();
+        let session = TaggedRuntimeID::<Session>::new();
+
+        assert_ne!(connection.retag::<Session>(), session);
+        assert_eq!(connection.retag::<Session>().retag::<Connection>(), connection);
+        assert_eq!(core::mem::size_of::<TaggedRuntimeID<Session>>(), core::mem::size_of::<RuntimeID>());
+    }
+
+    #[test]
+    fn niche() {
+        assert_eq!(core::mem::size_of::<RuntimeID>(), core::mem::size_of::<usize>());
+        assert_eq!(core::mem::align_of::<RuntimeID>(), core::mem::align_of::<usize>());
+        assert_eq!(core::mem::size_of::<Option<RuntimeID>>(), core::mem::size_of::<RuntimeID>());
+    }
+
+    #[test]
+    fn program_order() {
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let mut last = RuntimeID::new();
+                    for i in 0..10000 {
+                        let next = if i % 2 == 0 { RuntimeID::new() } else { RuntimeID::new_ordered() };
+                        assert!(last < next);
+                        last = next;
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn happens_before() {
+        // Pass an ID around a ring of threads. Receiving it happens-after it was created, so every ID created after
+        // receiving must be greater, however many other threads are creating IDs at the same time.
+        const THREADS: usize = 4;
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..THREADS).map(|_| mpsc::channel::<RuntimeID>()).unzip();
+        std::thread::scope(|scope| {
+            for (index, receiver) in receivers.into_iter().enumerate() {
+                let next = senders[(index + 1) % THREADS].clone();
+                scope.spawn(move || {
+                    for received in receiver.iter().take(2500) {
+                        let id = RuntimeID::new();
+                        assert!(received < id);
+                        let _ = next.send(id);
+                    }
+                });
+            }
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10000 {
+                        RuntimeID::new();
+                    }
+                });
+            }
+            senders[0].send(RuntimeID::new()).unwrap();
+        });
+    }
+
+    #[test]
+    fn ordered_synchronizes() {
+        // The writer stores `n` and then creates its nth ID. Each time the reader creates an ID it loads the stored
+        // value, and afterwards every writer ID below the reader's must have been stored before that load.
+        const ITERATIONS: usize = 100000;
+        let data = AtomicUsize::new(0);
+        let (written, read) = std::thread::scope(|scope| {
+            let writer = scope.spawn(|| {
+                (1..=ITERATIONS)
+                    .map(|n| {
+                        data.store(n, Ordering::Relaxed);
+                        RuntimeID::new_ordered()
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let reader = scope.spawn(|| {
+                (0..ITERATIONS)
+                    .map(|_| (RuntimeID::new_ordered(), data.load(Ordering::Relaxed)))
+                    .collect::<Vec<_>>()
+            });
+            (writer.join().unwrap(), reader.join().unwrap())
+        });
+
+        assert!(written.windows(2).all(|pair| pair[0] < pair[1]));
+        for (id, seen) in read {
+            let writes_before = written.partition_point(|&written| written < id);
+            assert!(seen >= writes_before, "{id} was created after {writes_before} writes but saw {seen}");
+        }
+    }
+
+    #[test]
+    fn ord() {
+        let ids: Vec<RuntimeID> = (0..100).map(|_| RuntimeID::new()).collect();
+        let mut sorted = ids.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, ids);
+
+        let map: std::collections::BTreeMap<RuntimeID, usize> = ids.iter().copied().zip(0..).collect();
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), (0..100).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::exhaustion::{exhaustion_policy, Exhausted};
+use crate::TaggedRuntimeID;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many IDs a thread reserves at once unless changed with [`set_local_block_size`].
+pub const DEFAULT_LOCAL_BLOCK_SIZE: usize = 1024;
+
+static BLOCK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_LOCAL_BLOCK_SIZE);
+
+std::thread_local! {
+    /// The unused part of this thread's current block, as `next..end`.
+    static BLOCK: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Sets how many IDs each thread reserves from the global counter when
+/// [`RuntimeID::new_local`](TaggedRuntimeID::new_local) runs out. A size of zero is treated as one.
+///
+/// Threads pick up the new size the next time they refill their block.
+pub fn set_local_block_size(size: usize) {
+    BLOCK_SIZE.store(size.max(1), Ordering::Relaxed);
+}
+
+/// Returns how many IDs each thread reserves at once.
+pub fn local_block_size() -> usize {
+    BLOCK_SIZE.load(Ordering::Relaxed)
+}
+
+impl<Tag> TaggedRuntimeID<Tag> {
+    /// Creates a new unique RuntimeID from a block of IDs reserved by the current thread.
+    ///
+    /// Each thread reserves [`local_block_size`] IDs from the global counter at a time and hands them out without
+    /// touching shared memory, which avoids contention when many threads create IDs at once. IDs are still unique
+    /// across every constructor, but IDs from different threads are no longer created in counter order.
+    ///
+    /// If every ID has already been handed out the current [`ExhaustionPolicy`](crate::ExhaustionPolicy) decides
+    /// what happens.
+    ///
+    /// # Example
+    /// ```
+    /// let a = runtime_id::RuntimeID::new_local();
+    /// let b = runtime_id::RuntimeID::new();
+    /// assert_ne!(a, b);
+    /// ```
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn new_local() -> Self {
+        match next_local() {
+            Ok(id) => Self::from_counter(id).track(),
+            Err(Exhausted) => Self::from_counter(exhaustion_policy().handle(crate::exhaustion::LIMIT)).track(),
+        }
+    }
+
+    /// Like [`new_local`](Self::new_local), but returns [`Exhausted`] if every ID has already been handed out.
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn try_new_local() -> Result<Self, Exhausted> {
+        Ok(Self::from_counter(next_local()?).track())
+    }
+}
+
+#[inline]
+pub(crate) fn next_local() -> Result<usize, Exhausted> {
+    #[cfg(feature = "testing")]
+    if let Some(id) = crate::testing::try_take(1) {
+        return id;
+    }
+    // Thread locals are unavailable while the thread is being torn down, so fall back to the global counter.
+    BLOCK.try_with(|block| {
+        let (next, end) = block.get();
+        if next != end {
+            block.set((next + 1, end));
+            return Ok(next);
+        }
+        refill(block)
+    })
+    .unwrap_or_else(|_| crate::take(Ordering::Relaxed))
+}
+
+#[cold]
+fn refill(block: &Cell<(usize, usize)>) -> Result<usize, Exhausted> {
+    let size = local_block_size();
+    match crate::reserve(size) {
+        Ok(first) => {
+            block.set((first + 1, first + size));
+            Ok(first)
+        }
+        // There may still be fewer than a block's worth of IDs left.
+        Err(Exhausted) => crate::take(Ordering::Relaxed),
+    }
+}
+
+/// Drops the rest of the current thread's block, which a forked child shares with its parent.
+#[cfg(all(feature = "fork-safe", unix))]
+pub(crate) fn forget_block() {
+    let _ = BLOCK.try_with(|block| block.set((0, 0)));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{local_block_size, set_local_block_size, RuntimeID};
+    use std::collections::HashSet;
+    use std::sync::{Mutex, PoisonError};
+    use std::vec::Vec;
+
+    /// Held by tests that change the block size or depend on it staying the same.
+    static BLOCK_SIZE: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn unique_across_threads() {
+        let ids: Vec<RuntimeID> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|thread| {
+                    scope.spawn(move || {
+                        (0..10000)
+                            .map(|i| if (thread + i) % 3 == 0 { RuntimeID::new() } else { RuntimeID::new_local() })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    }
+
+    #[test]
+    fn contiguous_within_block() {
+        let _size = BLOCK_SIZE.lock().unwrap_or_else(PoisonError::into_inner);
+        std::thread::spawn(|| {
+            let first = RuntimeID::new_local().id.get();
+            let size = local_block_size();
+            for offset in 1..size {
+                assert_eq!(RuntimeID::new_local().id.get(), first + offset);
+            }
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn block_size() {
+        let _size = BLOCK_SIZE.lock().unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(local_block_size(), crate::DEFAULT_LOCAL_BLOCK_SIZE);
+        set_local_block_size(0);
+        assert_eq!(local_block_size(), 1);
+        set_local_block_size(crate::DEFAULT_LOCAL_BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::exhaustion::Exhausted;
+use crate::TaggedRuntimeID;
+use core::fmt;
+use core::marker::PhantomData;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A [`TaggedRuntimeID`] that's only created the first time it's asked for. The tag defaults to `()`, giving a
+/// [`RuntimeID`](crate::RuntimeID).
+///
+/// [`new`](Self::new) is `const`, so a `OnceRuntimeID` can initialize a `static` or sit in a struct that's cheap to
+/// create, and only takes an ID from the global counter when [`get`](Self::get) is first called. Every call after
+/// that returns the same ID. Threads racing on the first call each take an ID, but a compare-and-swap picks one
+/// winner that all of them return; the others are skipped, never handed out.
+///
+/// # Example
+/// ```
+/// use runtime_id::{OnceRuntimeID, RuntimeID};
+///
+/// static SUBSYSTEM: OnceRuntimeID = OnceRuntimeID::new();
+///
+/// assert_eq!(SUBSYSTEM.assigned(), None);
+/// let id: RuntimeID = SUBSYSTEM.get();
+/// assert_eq!(SUBSYSTEM.get(), id);
+/// assert_eq!(SUBSYSTEM.assigned(), Some(id));
+/// ```
+pub struct OnceRuntimeID<Tag = ()> {
+    id: AtomicUsize,
+    tag: PhantomData<fn() -> Tag>,
+}
+
+impl<Tag> OnceRuntimeID<Tag> {
+    /// Creates a cell without an ID.
+    #[inline]
+    pub const fn new() -> Self {
+        OnceRuntimeID { id: AtomicUsize::new(0), tag: PhantomData }
+    }
+
+    /// Returns this cell's ID, creating it with [`RuntimeID::new`](TaggedRuntimeID::new) if it doesn't have one yet.
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn get(&self) -> TaggedRuntimeID<Tag> {
+        match self.assigned() {
+            Some(id) => id,
+            None => self.assign(TaggedRuntimeID::new()),
+        }
+    }
+
+    /// Returns this cell's ID, creating it with [`RuntimeID::try_new`](TaggedRuntimeID::try_new) if it doesn't have
+    /// one yet, or returns [`Exhausted`] if the counter has run out.
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn try_get(&self) -> Result<TaggedRuntimeID<Tag>, Exhausted> {
+        match self.assigned() {
+            Some(id) => Ok(id),
+            None => Ok(self.assign(TaggedRuntimeID::try_new()?)),
+        }
+    }
+
+    /// Returns this cell's ID if it has been created, without creating one.
+    #[inline]
+    pub fn assigned(&self) -> Option<TaggedRuntimeID<Tag>> {
+        NonZeroUsize::new(self.id.load(Ordering::Acquire)).map(TaggedRuntimeID::from_raw)
+    }
+
+    /// Consumes the cell and returns its ID if it has been created.
+    #[inline]
+    pub fn into_inner(self) -> Option<TaggedRuntimeID<Tag>> {
+        NonZeroUsize::new(self.id.into_inner()).map(TaggedRuntimeID::from_raw)
+    }
+
+    #[cold]
+    fn assign(&self, id: TaggedRuntimeID<Tag>) -> TaggedRuntimeID<Tag> {
+        match self.id.compare_exchange(0, id.id.get(), Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => id,
+            Err(winner) => {
+                #[cfg(all(feature = "track-origin", debug_assertions))]
+                crate::origin::clear(id.id.get());
+                match NonZeroUsize::new(winner) {
+                    Some(winner) => TaggedRuntimeID::from_raw(winner),
+                    None => unreachable!("a failed exchange from zero can't have seen zero"),
+                }
+            }
+        }
+    }
+}
+
+impl<Tag> Default for OnceRuntimeID<Tag> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shows the ID if it has been created, without creating one.
+impl<Tag> fmt::Debug for OnceRuntimeID<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OnceRuntimeID").field(&self.assigned()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use crate::{OnceRuntimeID, RuntimeID, TaggedRuntimeID};
+    use std::vec::Vec;
+
+    static STATIC: OnceRuntimeID = OnceRuntimeID::new();
+
+    #[test]
+    fn lazy() {
+        let once = OnceRuntimeID::<()>::new();
+        assert_eq!(once.assigned(), None);
+
+        let before = RuntimeID::new();
+        let id = once.get();
+        assert!(id > before);
+        assert_eq!(once.get(), id);
+        assert_eq!(once.try_get(), Ok(id));
+        assert_eq!(once.assigned(), Some(id));
+        assert_eq!(once.into_inner(), Some(id));
+        assert_eq!(OnceRuntimeID::<()>::default().into_inner(), None);
+    }
+
+    #[test]
+    fn in_static() {
+        let id = STATIC.get();
+        assert_eq!(std::thread::spawn(|| STATIC.get()).join().unwrap(), id);
+    }
+
+    #[test]
+    fn tagged() {
+        struct Session;
+        static SESSION: OnceRuntimeID<Session> = OnceRuntimeID::new();
+
+        let id: TaggedRuntimeID<Session> = SESSION.get();
+        assert_eq!(SESSION.assigned(), Some(id));
+        assert_eq!(SESSION.try_get(), Ok(id));
+    }
+
+    #[test]
+    fn race() {
+        for _ in 0..100 {
+            let once = OnceRuntimeID::new();
+            let ids: Vec<RuntimeID> = std::thread::scope(|scope| {
+                let threads: Vec<_> = (0..4).map(|_| scope.spawn(|| once.get())).collect();
+                threads.into_iter().map(|thread| thread.join().unwrap()).collect()
+            });
+            assert!(ids.iter().all(|&id| id == ids[0]));
+            assert_eq!(once.assigned(), Some(ids[0]));
+        }
+    }
+
+    #[test]
+    fn debug() {
+        let once = OnceRuntimeID::<()>::new();
+        assert_eq!(std::format!("{once:?}"), "OnceRuntimeID(None)");
+        let id = once.get();
+        assert_eq!(std::format!("{once:?}"), std::format!("OnceRuntimeID(Some({id:?}))"));
+    }
+}
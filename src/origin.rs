@@ -0,0 +1,120 @@
+use crate::{BuildRuntimeIdHasher, TaggedRuntimeID};
+use core::hash::BuildHasherDefault;
+use core::panic::Location;
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use std::time::Instant;
+
+static ORIGINS: RwLock<HashMap<usize, Origin, BuildRuntimeIdHasher>> =
+    RwLock::new(HashMap::with_hasher(BuildHasherDefault::new()));
+
+/// Where and when an ID was created, recorded with the `track-origin` feature in debug builds.
+#[derive(Clone, Copy, Debug)]
+pub struct Origin {
+    location: &'static Location<'static>,
+    created: Instant,
+}
+
+impl Origin {
+    /// Returns the source location that created the ID.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns when the ID was created.
+    pub fn created(&self) -> Instant {
+        self.created
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(crate) fn record(id: usize, location: &'static Location<'static>) {
+    let origin = Origin { location, created: Instant::now() };
+    ORIGINS.write().unwrap_or_else(PoisonError::into_inner).insert(id, origin);
+}
+
+pub(crate) fn clear(id: usize) {
+    ORIGINS.write().unwrap_or_else(PoisonError::into_inner).remove(&id);
+}
+
+pub(crate) fn get(id: usize) -> Option<Origin> {
+    ORIGINS.read().unwrap_or_else(PoisonError::into_inner).get(&id).copied()
+}
+
+impl<Tag> TaggedRuntimeID<Tag> {
+    /// Returns where and when this ID was created, if that was recorded.
+    ///
+    /// Origins are recorded by [`new`](Self::new), [`try_new`](Self::try_new), [`new_ordered`](Self::new_ordered),
+    /// [`new_in`](Self::new_in) and the thread-local constructors, but only in debug builds. IDs taken from an
+    /// [`IdRange`](crate::IdRange) have no origin. An [`OwnedRuntimeID`](crate::OwnedRuntimeID) forgets its origin
+    /// when dropped; other IDs keep theirs for the rest of the run.
+    ///
+    /// # Example
+    /// ```
+    /// let id = runtime_id::RuntimeID::new();
+    /// if let Some(origin) = id.origin() {
+    ///     assert_eq!(origin.location().line(), line!() - 2);
+    /// }
+    /// ```
+    pub fn origin(&self) -> Option<Origin> {
+        get(self.id.get())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{OwnedRuntimeID, RuntimeID};
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn records_caller() {
+        let before = std::time::Instant::now();
+        let once = crate::OnceRuntimeID::<()>::new();
+        let source = crate::SequenceSource::new();
+        let ids = [
+            (RuntimeID::new(), line!()),
+            (RuntimeID::try_new().unwrap(), line!()),
+            (RuntimeID::new_ordered(), line!()),
+            (RuntimeID::new_local(), line!()),
+            (RuntimeID::new_in(&source), line!()),
+            (once.get(), line!()),
+        ];
+
+        for (id, line) in ids {
+            let origin = id.origin().unwrap();
+            assert_eq!(origin.location().file(), file!());
+            assert_eq!(origin.location().line(), line);
+            assert!(origin.created() >= before);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn owned_forgets_origin() {
+        let owned = OwnedRuntimeID::new();
+        let id = owned.id();
+        assert_eq!(id.origin().unwrap().location().line(), line!() - 2);
+        drop(owned);
+        assert!(id.origin().is_none());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn debug() {
+        let id = RuntimeID::new();
+        let location = id.origin().unwrap().location();
+        assert!(std::format!("{:?}", id).ends_with(&std::format!(", created at {})", location)));
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[test]
+    fn release_records_nothing() {
+        assert!(RuntimeID::new().origin().is_none());
+        assert!(OwnedRuntimeID::new().id().origin().is_none());
+    }
+
+    #[test]
+    fn reserved_ids_have_no_origin() {
+        assert!(RuntimeID::reserve(1).next().unwrap().origin().is_none());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{BuildRuntimeIdHasher, RuntimeID};
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{BuildHasherDefault, Hash, Hasher};
+use std::collections::HashSet;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+use std::vec::Vec;
+
+static LIVE: Mutex<HashSet<usize, BuildRuntimeIdHasher>> = Mutex::new(HashSet::with_hasher(BuildHasherDefault::new()));
+
+/// A [`RuntimeID`] with a lifetime, tracked in a registry of live IDs until it's dropped.
+///
+/// Unlike [`RuntimeID`] this isn't `Copy`, so each ID has exactly one owner. The registry can be queried with
+/// [`live_count`](Self::live_count) and [`live_ids`](Self::live_ids), for example to assert that a test left no IDs
+/// behind. Dropping the ID also clears its label and origin.
+///
+/// # Example
+/// ```
+/// use runtime_id::OwnedRuntimeID;
+///
+/// let owned = OwnedRuntimeID::new();
+/// let id = owned.id();
+/// assert!(OwnedRuntimeID::live_ids().contains(&id));
+///
+/// drop(owned);
+/// assert!(!OwnedRuntimeID::live_ids().contains(&id));
+/// ```
+pub struct OwnedRuntimeID {
+    id: RuntimeID,
+}
+
+impl OwnedRuntimeID {
+    /// Creates a new unique ID and registers it as live.
+    #[allow(clippy::new_without_default)]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn new() -> Self {
+        let id = RuntimeID::new();
+        live().insert(id.id.get());
+        OwnedRuntimeID { id }
+    }
+
+    /// Returns the plain ID, which stays valid to compare and hash after this handle is dropped.
+    #[inline]
+    pub fn id(&self) -> RuntimeID {
+        self.id
+    }
+
+    /// Returns how many owned IDs are currently live.
+    pub fn live_count() -> usize {
+        live().len()
+    }
+
+    /// Returns every currently live owned ID, oldest first.
+    pub fn live_ids() -> Vec<RuntimeID> {
+        let mut ids: Vec<RuntimeID> = live().iter().map(|&id| RuntimeID::from_counter(id)).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Writes the `Debug` form of every live owned ID to `out`, one per line and oldest first. With the `labels`
+    /// and `track-origin` features this includes where each ID came from, which makes it useful for reporting leaks
+    /// at shutdown.
+    pub fn dump_live(out: &mut impl io::Write) -> io::Result<()> {
+        for id in Self::live_ids() {
+            writeln!(out, "{:?}", id)?;
+        }
+        Ok(())
+    }
+}
+
+fn live() -> std::sync::MutexGuard<'static, HashSet<usize, BuildRuntimeIdHasher>> {
+    LIVE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Drop for OwnedRuntimeID {
+    fn drop(&mut self) {
+        live().remove(&self.id.id.get());
+        self.id.clear_label();
+        #[cfg(feature = "track-origin")]
+        crate::origin::clear(self.id.id.get());
+    }
+}
+
+impl Borrow<RuntimeID> for OwnedRuntimeID {
+    fn borrow(&self) -> &RuntimeID {
+        &self.id
+    }
+}
+
+impl fmt::Debug for OwnedRuntimeID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.id, f)
+    }
+}
+
+impl fmt::Display for OwnedRuntimeID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.id, f)
+    }
+}
+
+impl PartialEq for OwnedRuntimeID {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for OwnedRuntimeID {}
+
+impl PartialOrd for OwnedRuntimeID {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OwnedRuntimeID {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl Hash for OwnedRuntimeID {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{OwnedRuntimeID, RuntimeIdSet};
+    use std::string::String;
+    use std::vec::Vec;
+
+    #[test]
+    fn registry() {
+        let owned: Vec<OwnedRuntimeID> = (0..10).map(|_| OwnedRuntimeID::new()).collect();
+        let ids: Vec<_> = owned.iter().map(OwnedRuntimeID::id).collect();
+        assert!(OwnedRuntimeID::live_count() >= 10);
+
+        let live = OwnedRuntimeID::live_ids();
+        assert!(ids.iter().all(|id| live.contains(id)));
+        assert!(live.windows(2).all(|pair| pair[0] < pair[1]));
+
+        drop(owned);
+        let live = OwnedRuntimeID::live_ids();
+        assert!(ids.iter().all(|id| !live.contains(id)));
+    }
+
+    #[test]
+    fn dump() {
+        let owned = OwnedRuntimeID::new();
+        owned.id().set_label("leaky");
+
+        let mut out = Vec::new();
+        OwnedRuntimeID::dump_live(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().any(|line| line.starts_with(&std::format!("RuntimeID({}", owned))));
+
+        let _id = owned.id();
+        drop(owned);
+        #[cfg(feature = "labels")]
+        assert_eq!(_id.label(), None);
+    }
+
+    #[test]
+    fn borrow() {
+        let owned = OwnedRuntimeID::new();
+        let id = owned.id();
+        let set: std::collections::HashSet<OwnedRuntimeID, crate::BuildRuntimeIdHasher> = [owned].into_iter().collect();
+        assert!(set.contains(&id));
+        let _: RuntimeIdSet = set.iter().map(OwnedRuntimeID::id).collect();
+    }
+}
@@ -0,0 +1,355 @@
+//! IDs that stay unique across restarts, handed out from memory in durably leased blocks.
+//!
+//! A [`PersistentCounter`] keeps a high-water mark in a small file: no ID at or above the mark has been handed out.
+//! IDs come from an in-memory block, and each time a block runs out the mark is raised by a whole block and written
+//! back before any ID from the new block is returned. Only one write and sync is paid per block, so most IDs cost
+//! no more than an uncontended lock.
+//!
+//! # Crash safety
+//! The mark is written to a staging file that is synced and then renamed over the counter file, so the file always
+//! holds either the old or the new mark, never a mix of the two. On Unix the directory is synced as well so the
+//! rename itself survives a power loss. After a crash, opening the counter again continues from the stored mark,
+//! which is past every ID handed out before the crash. The unused rest of the last block is skipped, not reissued.
+//!
+//! A counter file may only be used by one `PersistentCounter` at a time. [`PersistentCounter::open`] holds a lock
+//! on a `.lock` file next to it and fails with [`io::ErrorKind::WouldBlock`] if another counter already has it.
+
+use crate::exhaustion::{Exhausted, LIMIT64};
+use crate::source::IdSource;
+use core::num::{NonZeroU64, NonZeroUsize};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Identifies a counter file and its layout.
+const MAGIC: u64 = u64::from_le_bytes(*b"RTIDHWM1");
+
+const FILE_LEN: usize = 16;
+
+/// A counter whose IDs are never reissued, even after the process restarts or crashes. See the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct PersistentCounter {
+    path: PathBuf,
+    block_size: u64,
+    block: Mutex<Block>,
+    _lock: File,
+}
+
+/// The leased IDs not yet handed out, `next..end`. `end` is the mark stored in the file.
+#[derive(Debug)]
+struct Block {
+    next: u64,
+    end: u64,
+}
+
+/// Why a [`PersistentCounter`] couldn't hand out an ID.
+#[derive(Debug)]
+pub enum PersistError {
+    /// Every ID up to `i64::MAX` has been handed out.
+    Exhausted,
+    /// [`try_reserve_u64`](PersistentCounter::try_reserve_u64) was asked for zero IDs, which have no first ID.
+    Empty,
+    /// The new high-water mark couldn't be written, so no further IDs can be handed out safely.
+    Io(io::Error),
+}
+
+impl core::fmt::Display for PersistError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PersistError::Exhausted => Exhausted.fmt(f),
+            PersistError::Empty => f.write_str("a RuntimeID reservation must contain at least one ID"),
+            PersistError::Io(error) => write!(f, "failed to persist RuntimeID high-water mark: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PersistError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PersistError::Exhausted | PersistError::Empty => None,
+            PersistError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl PersistentCounter {
+    /// Opens the counter file at `path`, creating it if it doesn't exist, and leases IDs `block_size` at a time.
+    ///
+    /// No IDs are leased until the first one is needed. Fails with [`io::ErrorKind::InvalidData`] if the file
+    /// isn't a counter file, and with [`io::ErrorKind::WouldBlock`] if another counter is using it.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is zero.
+    pub fn open(path: impl AsRef<Path>, block_size: u64) -> io::Result<Self> {
+        assert!(block_size > 0, "block size must be at least one");
+        let path = path.as_ref().to_path_buf();
+
+        let lock = OpenOptions::new().write(true).create(true).truncate(false).open(sibling(&path, ".lock"))?;
+        lock.try_lock()?;
+
+        let mark = match fs::read(&path) {
+            Ok(bytes) => parse(&bytes)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => 1,
+            Err(error) => return Err(error),
+        };
+        Ok(PersistentCounter { path, block_size, block: Mutex::new(Block { next: mark, end: mark }), _lock: lock })
+    }
+
+    /// Takes the next ID, leasing and persisting a new block first if the current one is used up.
+    #[inline]
+    pub fn try_next_u64(&self) -> Result<NonZeroU64, PersistError> {
+        self.try_reserve_u64(1)
+    }
+
+    /// Takes `count` consecutive IDs at once and returns the first of them, or returns
+    /// [`PersistError::Exhausted`] if fewer than `count` IDs are left. If the current block doesn't have enough IDs
+    /// left, a single lease covering all of them is persisted first. Asking for zero IDs fails with
+    /// [`PersistError::Empty`].
+    pub fn try_reserve_u64(&self, count: u64) -> Result<NonZeroU64, PersistError> {
+        if count == 0 {
+            return Err(PersistError::Empty);
+        }
+        let mut block = self.block.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let needed = block.next.checked_add(count).filter(|&needed| needed <= LIMIT64 + 1);
+        let needed = needed.ok_or(PersistError::Exhausted)?;
+        if needed > block.end {
+            let end = needed.max(block.end.saturating_add(self.block_size)).min(LIMIT64 + 1);
+            write_mark(&self.path, end).map_err(PersistError::Io)?;
+            block.end = end;
+        }
+
+        let first = block.next;
+        block.next = needed;
+        Ok(NonZeroU64::new(first).expect("counter files start at one"))
+    }
+
+    /// Returns the number of IDs leased at a time.
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Returns the path of the counter file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// A block that can't be persisted is reported as [`Exhausted`], since the counter can't hand out any more IDs safely.
+impl IdSource for PersistentCounter {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        self.try_next_u64().map_err(|_| Exhausted)?.try_into().map_err(|_| Exhausted)
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = OsString::from(path);
+    sibling.push(suffix);
+    sibling.into()
+}
+
+fn parse(bytes: &[u8]) -> io::Result<u64> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a RuntimeID high-water mark file");
+    let bytes: &[u8; FILE_LEN] = bytes.try_into().map_err(|_| invalid())?;
+    let (magic, mark) = bytes.split_at(8);
+    if u64::from_le_bytes(magic.try_into().unwrap()) != MAGIC {
+        return Err(invalid());
+    }
+    match u64::from_le_bytes(mark.try_into().unwrap()) {
+        mark @ 1..=LIMIT64 => Ok(mark),
+        mark if mark == LIMIT64 + 1 => Ok(mark),
+        _ => Err(invalid()),
+    }
+}
+
+/// Durably replaces the mark stored at `path`. When this returns `Ok`, the new mark survives a crash.
+fn write_mark(path: &Path, mark: u64) -> io::Result<()> {
+    let staging = sibling(path, ".tmp");
+    let mut file = File::create(&staging)?;
+    file.write_all(&MAGIC.to_le_bytes())?;
+    file.write_all(&mark.to_le_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&staging, path)?;
+
+    #[cfg(unix)]
+    {
+        let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sibling, write_mark, PersistError, PersistentCounter, MAGIC};
+    use crate::child;
+    use crate::exhaustion::LIMIT64;
+    use crate::RuntimeID;
+    use std::io;
+    use std::path::Path;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    fn remove(path: &Path) {
+        for path in [path.to_path_buf(), sibling(path, ".lock"), sibling(path, ".tmp")] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    fn next(counter: &PersistentCounter) -> u64 {
+        counter.try_next_u64().unwrap().get()
+    }
+
+    #[test]
+    fn leases_blocks() {
+        let path = child::temp_path("hwm");
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert!(!path.exists());
+
+        assert_eq!(next(&counter), 1);
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 11);
+        assert_eq!((2..=10).map(|_| next(&counter)).collect::<Vec<_>>(), (2..=10).collect::<Vec<_>>());
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 11);
+        assert_eq!(next(&counter), 11);
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 21);
+        assert_eq!(RuntimeID::new_in(&counter).to_string(), "12");
+
+        drop(counter);
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert_eq!(next(&counter), 21);
+        drop(counter);
+        remove(&path);
+    }
+
+    #[test]
+    fn reserves_across_blocks() {
+        let path = child::temp_path("hwm");
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert_eq!(counter.try_reserve_u64(4).unwrap().get(), 1);
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 11);
+        assert_eq!(counter.try_reserve_u64(25).unwrap().get(), 5);
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 30);
+        assert_eq!(next(&counter), 30);
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 40);
+        assert!(matches!(counter.try_reserve_u64(LIMIT64), Err(PersistError::Exhausted)));
+        assert_eq!(next(&counter), 31);
+        assert!(matches!(counter.try_reserve_u64(0), Err(PersistError::Empty)));
+        assert_eq!(next(&counter), 32);
+        drop(counter);
+        remove(&path);
+    }
+
+    #[test]
+    fn one_counter_per_file() {
+        let path = child::temp_path("hwm");
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        let error = PersistentCounter::open(&path, 10).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        drop(counter);
+        PersistentCounter::open(&path, 10).unwrap();
+        remove(&path);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = child::temp_path("hwm");
+        let mut valid = Vec::from(MAGIC.to_le_bytes());
+        valid.extend(5u64.to_le_bytes());
+
+        let mut zero = Vec::from(MAGIC.to_le_bytes());
+        zero.extend(0u64.to_le_bytes());
+        for contents in [&b"definitely not a counter"[..], &valid[..15], &[0; 16], &zero] {
+            std::fs::write(&path, contents).unwrap();
+            let error = PersistentCounter::open(&path, 10).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+
+        std::fs::write(&path, &valid).unwrap();
+        assert_eq!(next(&PersistentCounter::open(&path, 10).unwrap()), 5);
+        remove(&path);
+    }
+
+    #[test]
+    fn exhaustion() {
+        let path = child::temp_path("hwm");
+        write_mark(&path, LIMIT64 - 1).unwrap();
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert_eq!(next(&counter), LIMIT64 - 1);
+        assert_eq!(next(&counter), LIMIT64);
+        assert!(matches!(counter.try_next_u64(), Err(PersistError::Exhausted)));
+        drop(counter);
+
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert!(matches!(counter.try_next_u64(), Err(PersistError::Exhausted)));
+        drop(counter);
+        remove(&path);
+    }
+
+    #[test]
+    fn failed_lease_hands_out_nothing() {
+        let directory = child::temp_path("hwm");
+        std::fs::create_dir(&directory).unwrap();
+        let path = directory.join("counter");
+        let counter = PersistentCounter::open(&path, 2).unwrap();
+        assert_eq!(next(&counter), 1);
+        assert_eq!(next(&counter), 2);
+
+        // A directory in place of the staging file makes the next lease fail before the mark is replaced.
+        std::fs::create_dir(sibling(&path, ".tmp")).unwrap();
+        assert!(matches!(counter.try_next_u64(), Err(PersistError::Io(_))));
+        assert!(RuntimeID::try_new_in(&counter).is_err());
+        std::fs::remove_dir(sibling(&path, ".tmp")).unwrap();
+        assert_eq!(next(&counter), 3);
+
+        drop(counter);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn ignores_interrupted_write() {
+        let path = child::temp_path("hwm");
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert_eq!(next(&counter), 1);
+        drop(counter);
+
+        // A crash while writing the next mark leaves a partial staging file behind and the old mark in place.
+        std::fs::write(sibling(&path, ".tmp"), &MAGIC.to_le_bytes()[..5]).unwrap();
+        let counter = PersistentCounter::open(&path, 10).unwrap();
+        assert_eq!(next(&counter), 11);
+        assert_eq!(super::parse(&std::fs::read(&path).unwrap()).unwrap(), 21);
+        drop(counter);
+        remove(&path);
+    }
+
+    /// Child processes take IDs and abort partway through a block, so their counter is never dropped cleanly.
+    #[test]
+    fn never_reissues_after_crash() {
+        if let Some(path) = child::arg() {
+            let counter = PersistentCounter::open(path, 100).unwrap();
+            child::print_ids((0..250).map(|_| next(&counter)));
+            std::process::abort();
+        }
+
+        let path = child::temp_path("hwm");
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let output = child::command("persist::test::never_reissues_after_crash", &path).output().unwrap();
+            assert!(!output.status.success());
+            let run = child::ids(&output);
+
+            assert_eq!(run.len(), 250);
+            assert!(ids.iter().all(|&earlier| earlier < run[0]));
+            ids.extend(run);
+        }
+
+        // Each run skips the 50 IDs left in its last block.
+        let expected: Vec<u64> = (0..3).flat_map(|run| run * 300 + 1..=run * 300 + 250).collect();
+        assert_eq!(ids, expected);
+        assert_eq!(next(&PersistentCounter::open(&path, 100).unwrap()), 901);
+        remove(&path);
+    }
+}
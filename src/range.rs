@@ -0,0 +1,239 @@
+use crate::exhaustion::{exhaustion_policy, Exhausted};
+use crate::TaggedRuntimeID;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+/// A block of consecutive IDs reserved with [`RuntimeID::reserve`](TaggedRuntimeID::reserve).
+///
+/// The IDs belong to the range alone; nothing else will hand them out. Iterating yields them in ascending order
+/// from the front or descending order from the back.
+pub struct IdRange<Tag = ()> {
+    start: usize,
+    end: usize,
+    tag: PhantomData<fn() -> Tag>,
+}
+
+impl<Tag> IdRange<Tag> {
+    #[inline]
+    const fn from_raw(start: usize, end: usize) -> Self {
+        IdRange { start, end, tag: PhantomData }
+    }
+
+    /// Returns true if `id` is one of the IDs remaining in this range.
+    #[inline]
+    pub fn contains(&self, id: &TaggedRuntimeID<Tag>) -> bool {
+        (self.start..self.end).contains(&id.id.get())
+    }
+
+    /// Returns the ID at `index` without consuming it, or `None` if `index` is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<TaggedRuntimeID<Tag>> {
+        if index < self.len() {
+            Some(TaggedRuntimeID::from_counter(self.start + index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the lowest ID remaining in this range.
+    #[inline]
+    pub fn front(&self) -> Option<TaggedRuntimeID<Tag>> {
+        self.get(0)
+    }
+
+    /// Returns the highest ID remaining in this range.
+    #[inline]
+    pub fn back(&self) -> Option<TaggedRuntimeID<Tag>> {
+        self.len().checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    /// Splits the range in two, with the first `mid` IDs in the first half.
+    ///
+    /// # Panics
+    /// Panics if `mid` is greater than the length of the range.
+    ///
+    /// # Example
+    /// ```
+    /// let (a, b) = runtime_id::RuntimeID::reserve(10).split_at(4);
+    /// assert_eq!(a.len(), 4);
+    /// assert_eq!(b.len(), 6);
+    /// assert!(!a.contains(&b.front().unwrap()));
+    /// ```
+    pub fn split_at(self, mid: usize) -> (Self, Self) {
+        assert!(mid <= self.len(), "split index out of bounds");
+        let mid = self.start + mid;
+        (Self::from_raw(self.start, mid), Self::from_raw(mid, self.end))
+    }
+}
+
+impl<Tag> TaggedRuntimeID<Tag> {
+    /// Reserves `count` consecutive unique IDs at once.
+    ///
+    /// This costs a single atomic operation regardless of `count` when uncontended, which makes it much cheaper than
+    /// calling [`new`](Self::new) in a loop when creating many IDs.
+    ///
+    /// If fewer than `count` IDs are left the current [`ExhaustionPolicy`](crate::ExhaustionPolicy) decides what
+    /// happens. Under [`Saturate`](crate::ExhaustionPolicy::Saturate) an empty range is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use runtime_id::RuntimeID;
+    ///
+    /// let ids = RuntimeID::reserve(3);
+    /// assert_eq!(ids.len(), 3);
+    /// let ids: Vec<RuntimeID> = ids.collect();
+    /// assert_ne!(ids[0], ids[1]);
+    /// ```
+    pub fn reserve(count: usize) -> IdRange<Tag> {
+        match Self::try_reserve(count) {
+            Ok(range) => range,
+            Err(Exhausted) => exhaustion_policy().handle(IdRange::from_raw(0, 0)),
+        }
+    }
+
+    /// Like [`reserve`](Self::reserve), but returns [`Exhausted`] if fewer than `count` IDs are left.
+    pub fn try_reserve(count: usize) -> Result<IdRange<Tag>, Exhausted> {
+        crate::reserve(count).map(|start| IdRange::from_raw(start, start + count))
+    }
+}
+
+impl<Tag> Iterator for IdRange<Tag> {
+    type Item = TaggedRuntimeID<Tag>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.front()?;
+        self.start += 1;
+        Some(id)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start += n.min(self.len());
+        self.next()
+    }
+}
+
+impl<Tag> DoubleEndedIterator for IdRange<Tag> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let id = self.back()?;
+        self.end -= 1;
+        Some(id)
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.end -= n.min(self.len());
+        self.next_back()
+    }
+}
+
+impl<Tag> ExactSizeIterator for IdRange<Tag> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+impl<Tag> FusedIterator for IdRange<Tag> {}
+
+impl<Tag> Clone for IdRange<Tag> {
+    fn clone(&self) -> Self {
+        Self::from_raw(self.start, self.end)
+    }
+}
+
+impl<Tag> fmt::Debug for IdRange<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IdRange").field(&(self.start..self.end)).finish()
+    }
+}
+
+impl<Tag> PartialEq for IdRange<Tag> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.start..self.end) == (other.start..other.end)
+    }
+}
+impl<Tag> Eq for IdRange<Tag> {}
+
+#[cfg(test)]
+mod test {
+    use crate::{IdRange, RuntimeID};
+
+    #[test]
+    fn iterate() {
+        let range = RuntimeID::reserve(5);
+        let first = range.front().unwrap();
+        let last = range.back().unwrap();
+        let mut iter = range.clone();
+
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next(), Some(first));
+        assert_eq!(iter.next_back(), Some(last));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.by_ref().rev().last(), range.get(1));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn nth() {
+        let range = RuntimeID::reserve(10);
+        let mut iter = range.clone();
+        assert_eq!(iter.nth(3), range.get(3));
+        assert_eq!(iter.nth_back(2), range.get(7));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.nth(5), None);
+        assert_eq!(iter.len(), 0);
+    }
+
+    #[test]
+    fn disjoint() {
+        let a = RuntimeID::reserve(100);
+        let b = RuntimeID::reserve(100);
+        let single = RuntimeID::new();
+
+        assert!(a.clone().all(|id| !b.contains(&id) && id != single));
+        assert!(a.clone().all(|id| a.contains(&id)));
+        assert!(!a.contains(&single));
+    }
+
+    #[test]
+    fn split() {
+        let range = RuntimeID::reserve(10);
+        let (left, right) = range.clone().split_at(3);
+
+        assert_eq!(left.len(), 3);
+        assert_eq!(right.len(), 7);
+        assert_eq!(left.back(), range.get(2));
+        assert_eq!(right.front(), range.get(3));
+        assert!(left.chain(right).eq(range.clone()));
+
+        let (empty, all) = range.clone().split_at(0);
+        assert_eq!(empty.len(), 0);
+        assert_eq!(all, range);
+    }
+
+    #[test]
+    #[should_panic(expected = "split index out of bounds")]
+    fn split_out_of_bounds() {
+        RuntimeID::reserve(2).split_at(3);
+    }
+
+    #[test]
+    fn empty() {
+        let mut range: IdRange = RuntimeID::reserve(0);
+        assert_eq!(range.len(), 0);
+        assert_eq!(range.front(), None);
+        assert_eq!(range.back(), None);
+        assert_eq!(range.get(0), None);
+        assert_eq!(range.next(), None);
+    }
+}
@@ -0,0 +1,62 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::SystemTime;
+
+static TOKEN: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a random token that identifies the current run of the program.
+///
+/// The token is generated the first time it's needed and then stays the same for the rest of the run. Different
+/// runs get different tokens with overwhelming probability, so anything tagged with the token, such as a serialized
+/// ID, can be checked against the run that created it. With the `fork-safe` feature, a process created with `fork`
+/// counts as a new run and gets a new token.
+pub fn run_token() -> u64 {
+    match TOKEN.load(Ordering::Relaxed) {
+        0 => init(),
+        token => token,
+    }
+}
+
+#[cold]
+fn init() -> u64 {
+    let token = generate();
+    match TOKEN.compare_exchange(0, token, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => token,
+        Err(existing) => existing,
+    }
+}
+
+/// Forgets the current token so the next call to [`run_token`] generates a new one.
+#[cfg(all(feature = "fork-safe", unix))]
+pub(crate) fn reset() {
+    TOKEN.store(0, Ordering::Relaxed);
+}
+
+fn generate() -> u64 {
+    // RandomState is seeded by the operating system, the pid and time are mixed in as a belt and braces measure.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    hasher.finish().max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::run_token;
+
+    #[test]
+    fn stable() {
+        let token = run_token();
+        assert_ne!(token, 0);
+        assert_eq!(run_token(), token);
+        assert_eq!(std::thread::spawn(run_token).join().unwrap(), token);
+    }
+
+    #[test]
+    fn generate_differs() {
+        assert_ne!(super::generate(), super::generate());
+    }
+}
@@ -0,0 +1,119 @@
+//! Serde support that ties serialized IDs to the run that created them.
+//!
+//! IDs are serialized together with the [`run_token`]. Deserializing an ID serialized by a different run fails, so IDs
+//! can pass through channels, IPC and caches within a run without ever being confused with IDs from another one. The
+//! wire form also records which counter an ID came from, so a [`RuntimeID64`] can't be read back as a
+//! [`RuntimeID`](crate::RuntimeID) or the other way around.
+
+use crate::{run_token, RuntimeID64, TaggedRuntimeID};
+use core::num::{NonZeroU64, NonZeroUsize};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Which counter an ID came from.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Kind {
+    RuntimeID,
+    RuntimeID64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "RuntimeID")]
+struct Wire {
+    kind: Kind,
+    run: u64,
+    id: u64,
+}
+
+impl Wire {
+    fn new(kind: Kind, id: u64) -> Self {
+        Wire { kind, run: run_token(), id }
+    }
+
+    fn into_id<E: Error>(self, kind: Kind) -> Result<NonZeroU64, E> {
+        if self.kind != kind {
+            return Err(E::custom("RuntimeID and RuntimeID64 come from different counters"));
+        }
+        if self.run != run_token() {
+            return Err(E::custom("RuntimeID was created by a different run of the program"));
+        }
+        NonZeroU64::new(self.id).ok_or_else(|| E::custom("RuntimeID can't be zero"))
+    }
+}
+
+impl<Tag> Serialize for TaggedRuntimeID<Tag> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Wire::new(Kind::RuntimeID, self.id.get() as u64).serialize(serializer)
+    }
+}
+
+impl<'de, Tag> Deserialize<'de> for TaggedRuntimeID<Tag> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = Wire::deserialize(deserializer)?.into_id(Kind::RuntimeID)?;
+        usize::try_from(id.get())
+            .ok()
+            .and_then(NonZeroUsize::new)
+            .map(Self::from_raw)
+            .ok_or_else(|| D::Error::custom("RuntimeID is out of range"))
+    }
+}
+
+impl Serialize for RuntimeID64 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Wire::new(Kind::RuntimeID64, self.0.get()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RuntimeID64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Wire::deserialize(deserializer)?.into_id(Kind::RuntimeID64).map(RuntimeID64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{run_token, RuntimeID, RuntimeID64};
+    use std::format;
+    use std::string::ToString;
+
+    #[test]
+    fn round_trip() {
+        let id = RuntimeID::new();
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<RuntimeID>(&json).unwrap(), id);
+
+        let id = RuntimeID64::new();
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<RuntimeID64>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn other_run() {
+        let json = format!(r#"{{"kind":"RuntimeID","run":{},"id":1}}"#, run_token().wrapping_add(1));
+        let error = serde_json::from_str::<RuntimeID>(&json).unwrap_err();
+        assert!(error.to_string().contains("different run"));
+        let json = format!(r#"{{"kind":"RuntimeID64","run":{},"id":1}}"#, run_token().wrapping_add(1));
+        assert!(serde_json::from_str::<RuntimeID64>(&json).is_err());
+    }
+
+    #[test]
+    fn zero() {
+        let json = format!(r#"{{"kind":"RuntimeID","run":{},"id":0}}"#, run_token());
+        assert!(serde_json::from_str::<RuntimeID>(&json).is_err());
+        let json = format!(r#"{{"kind":"RuntimeID64","run":{},"id":0}}"#, run_token());
+        assert!(serde_json::from_str::<RuntimeID64>(&json).is_err());
+    }
+
+    #[test]
+    fn other_kind() {
+        let json = serde_json::to_string(&RuntimeID64::new()).unwrap();
+        let error = serde_json::from_str::<RuntimeID>(&json).unwrap_err();
+        assert!(error.to_string().contains("different counters"));
+
+        let json = serde_json::to_string(&RuntimeID::new()).unwrap();
+        assert!(serde_json::from_str::<RuntimeID64>(&json).is_err());
+
+        let json = format!(r#"{{"run":{},"id":1}}"#, run_token());
+        assert!(serde_json::from_str::<RuntimeID>(&json).is_err());
+    }
+}
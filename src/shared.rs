@@ -0,0 +1,271 @@
+//! A counter shared by every process on the host that maps the same file.
+//!
+//! [`SharedCounter`] keeps its counter in a small memory-mapped file. Every process that attaches increments the
+//! same atomic, so IDs are unique across all of them without a coordinating process.
+//!
+//! # Lifecycle
+//! - [`SharedCounter::create`] makes a new counter file starting at one. The file is fully initialized before it
+//!   appears at `path`, so other processes never see a half-written counter.
+//! - [`SharedCounter::open`] attaches to an existing counter file.
+//! - [`SharedCounter::open_or_create`] does whichever applies, and is safe for several processes to race on.
+//! - [`SharedCounter::remove`] deletes the file. Processes already attached keep sharing their counter, but anyone
+//!   creating a counter at `path` afterwards starts again from one, so only remove it once every cooperating process
+//!   is finished with its IDs.
+//!
+//! Dropping a `SharedCounter` unmaps the file but leaves it in place.
+
+use crate::exhaustion::{Exhausted, LIMIT64};
+use crate::source::IdSource;
+use core::num::{NonZeroU64, NonZeroUsize};
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// Identifies a counter file and the layout of its [`Header`].
+const MAGIC: u64 = u64::from_le_bytes(*b"RTIDSHM1");
+
+#[repr(C)]
+struct Header {
+    magic: u64,
+    next: AtomicU64,
+}
+
+const HEADER_LEN: usize = core::mem::size_of::<Header>();
+
+/// Numbers staging files so concurrent calls to [`SharedCounter::create`] in one process don't collide.
+static STAGING: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new file next to `path` to initialize a counter in before it's linked into place.
+fn staging_file(path: &Path) -> io::Result<(std::ffi::OsString, File)> {
+    loop {
+        let mut staging = std::ffi::OsString::from(path);
+        staging.push(std::format!(".{}.{}.tmp", std::process::id(), STAGING.fetch_add(1, Ordering::Relaxed)));
+        match OpenOptions::new().read(true).write(true).create_new(true).open(&staging) {
+            // Left behind by a process that crashed and had the same pid, so pick another name.
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            result => return result.map(|file| (staging, file)),
+        }
+    }
+}
+
+/// A counter shared between processes through a memory-mapped file. See the [module docs](self).
+pub struct SharedCounter {
+    header: NonNull<Header>,
+}
+
+// SAFETY: the mapping is only accessed through atomics and stays valid until drop.
+unsafe impl Send for SharedCounter {}
+unsafe impl Sync for SharedCounter {}
+
+impl SharedCounter {
+    /// Creates a new counter file at `path`, failing with [`io::ErrorKind::AlreadyExists`] if there is one.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let (staging, mut file) = staging_file(path)?;
+        let result = (|| {
+            file.write_all(&MAGIC.to_ne_bytes())?;
+            file.write_all(&1u64.to_ne_bytes())?;
+            // Linking fails if `path` exists, so the counter only appears once it's initialized.
+            fs::hard_link(&staging, path)
+        })();
+        let _ = fs::remove_file(&staging);
+        result?;
+        Self::map(&file)
+    }
+
+    /// Attaches to the existing counter file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::map(&OpenOptions::new().read(true).write(true).open(path)?)
+    }
+
+    /// Attaches to the counter file at `path`, creating it if it doesn't exist.
+    pub fn open_or_create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        loop {
+            match Self::create(path) {
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => match Self::open(path) {
+                    // Removed again before it could be opened, so try to create it once more.
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                    result => return result,
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Deletes the counter file at `path`. See the [module docs](self#lifecycle) for what this means for processes
+    /// still using it.
+    pub fn remove(path: impl AsRef<Path>) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn map(file: &File) -> io::Result<Self> {
+        if file.metadata()?.len() != HEADER_LEN as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a RuntimeID counter file"));
+        }
+
+        // SAFETY: mapping a file we hold open; the result is checked before use.
+        let address = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                HEADER_LEN,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        let counter = SharedCounter { header: NonNull::new(address.cast()).expect("mmap returned null") };
+        if counter.header().magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a RuntimeID counter file"));
+        }
+        Ok(counter)
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping is page aligned, at least `HEADER_LEN` long and lives until drop.
+        unsafe { self.header.as_ref() }
+    }
+
+    /// Takes the next ID, or returns [`Exhausted`] once all `i64::MAX` IDs have been handed out.
+    #[inline]
+    pub fn try_next_u64(&self) -> Result<NonZeroU64, Exhausted> {
+        let next = &self.header().next;
+        let id = next.fetch_add(1, Ordering::Relaxed);
+        if id > LIMIT64 {
+            next.store(LIMIT64 + 1, Ordering::Relaxed);
+            return Err(Exhausted);
+        }
+        NonZeroU64::new(id).ok_or(Exhausted)
+    }
+}
+
+impl IdSource for SharedCounter {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        self.try_next_u64()?.try_into().map_err(|_| Exhausted)
+    }
+}
+
+impl Drop for SharedCounter {
+    fn drop(&mut self) {
+        // SAFETY: unmapping the region mapped in `map`, which nothing references past this point.
+        unsafe {
+            libc::munmap(self.header.as_ptr().cast(), HEADER_LEN);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SharedCounter;
+    use crate::child;
+    use crate::RuntimeID;
+    use std::collections::HashSet;
+    use std::io;
+    use std::string::ToString;
+    use std::sync::Barrier;
+    use std::vec::Vec;
+
+    const IDS_PER_PROCESS: usize = 10000;
+
+    #[test]
+    fn lifecycle() {
+        let path = child::temp_path("shm");
+        assert_eq!(SharedCounter::open(&path).err().map(|error| error.kind()), Some(io::ErrorKind::NotFound));
+
+        let created = SharedCounter::create(&path).unwrap();
+        assert_eq!(SharedCounter::create(&path).err().map(|error| error.kind()), Some(io::ErrorKind::AlreadyExists));
+        let opened = SharedCounter::open(&path).unwrap();
+        let either = SharedCounter::open_or_create(&path).unwrap();
+
+        assert_eq!(created.try_next_u64().unwrap().get(), 1);
+        assert_eq!(opened.try_next_u64().unwrap().get(), 2);
+        assert_eq!(RuntimeID::new_in(&either).to_string(), "3");
+
+        SharedCounter::remove(&path).unwrap();
+        assert_eq!(opened.try_next_u64().unwrap().get(), 4);
+        let fresh = SharedCounter::open_or_create(&path).unwrap();
+        assert_eq!(fresh.try_next_u64().unwrap().get(), 1);
+        SharedCounter::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn racing_creators() {
+        for _ in 0..50 {
+            let path = child::temp_path("shm");
+            let barrier = Barrier::new(8);
+            std::thread::scope(|scope| {
+                for _ in 0..8 {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        SharedCounter::open_or_create(&path).unwrap().try_next_u64().unwrap();
+                    });
+                }
+            });
+            assert_eq!(SharedCounter::open(&path).unwrap().try_next_u64().unwrap().get(), 9);
+            SharedCounter::remove(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn ignores_stale_staging_files() {
+        let path = child::temp_path("shm");
+        let next = super::STAGING.load(core::sync::atomic::Ordering::Relaxed);
+        let stale: Vec<_> = (next..next + 3)
+            .map(|staging| std::format!("{}.{}.{staging}.tmp", path.display(), std::process::id()))
+            .collect();
+        for stale in &stale {
+            std::fs::write(stale, b"").unwrap();
+        }
+
+        SharedCounter::create(&path).unwrap();
+        SharedCounter::remove(&path).unwrap();
+        for stale in stale {
+            std::fs::remove_file(stale).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = child::temp_path("shm");
+        std::fs::write(&path, b"definitely not a counter").unwrap();
+        assert_eq!(SharedCounter::open(&path).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+        std::fs::write(&path, [0; 16]).unwrap();
+        assert_eq!(SharedCounter::open(&path).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unique_across_processes() {
+        if let Some(path) = child::arg() {
+            let counter = SharedCounter::open_or_create(path).unwrap();
+            child::print_ids((0..IDS_PER_PROCESS).map(|_| counter.try_next_u64().unwrap().get()));
+            return;
+        }
+
+        let path = child::temp_path("shm");
+        let children: Vec<_> =
+            (0..4).map(|_| child::command("shared::test::unique_across_processes", &path).spawn().unwrap()).collect();
+
+        let mut ids = Vec::new();
+        for process in children {
+            let output = process.wait_with_output().unwrap();
+            assert!(output.status.success());
+            ids.extend(child::ids(&output));
+        }
+
+        assert_eq!(ids.len(), 4 * IDS_PER_PROCESS);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        let counter = SharedCounter::open(&path).unwrap();
+        assert_eq!(counter.try_next_u64().unwrap().get(), 4 * IDS_PER_PROCESS as u64 + 1);
+        SharedCounter::remove(&path).unwrap();
+    }
+}
@@ -0,0 +1,340 @@
+//! Snowflake-style IDs that stay unique across cooperating processes.
+//!
+//! A [`SnowflakeID`] packs an optional timestamp, a node number and a per-node sequence into 64 bits, from the most
+//! significant bits down. As long as every process is given its own node number, processes can create IDs
+//! independently and exchange them without collisions.
+//!
+//! # Example
+//! ```
+//! use runtime_id::snowflake::{SnowflakeGenerator, SnowflakeLayout};
+//!
+//! let layout = SnowflakeLayout::new(0, 10, 40).unwrap();
+//! let worker_3 = SnowflakeGenerator::new(layout, 3).unwrap();
+//! let worker_4 = SnowflakeGenerator::new(layout, 4).unwrap();
+//!
+//! let a = worker_3.next().unwrap();
+//! let b = worker_4.next().unwrap();
+//! assert_ne!(a, b);
+//! assert_eq!(layout.decode(a).node, 3);
+//! ```
+
+use crate::atomic64::Atomic64;
+use core::fmt;
+
+/// How many bits of a [`SnowflakeID`] go to each part.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SnowflakeLayout {
+    timestamp_bits: u32,
+    node_bits: u32,
+    sequence_bits: u32,
+}
+
+impl SnowflakeLayout {
+    /// The classic layout: 41 bits of milliseconds, 10 bits of node and 12 bits of sequence.
+    pub const CLASSIC: SnowflakeLayout = SnowflakeLayout { timestamp_bits: 41, node_bits: 10, sequence_bits: 12 };
+
+    /// Creates a layout, checking that it fits in 63 bits so IDs stay positive when stored as `i64`, and that there
+    /// is at least one bit of sequence.
+    pub const fn new(timestamp_bits: u32, node_bits: u32, sequence_bits: u32) -> Result<Self, SnowflakeError> {
+        // Checking each field first keeps the sum from overflowing.
+        if sequence_bits == 0
+            || timestamp_bits > 63
+            || node_bits > 63
+            || sequence_bits > 63
+            || timestamp_bits + node_bits + sequence_bits > 63
+        {
+            return Err(SnowflakeError::InvalidLayout);
+        }
+        Ok(SnowflakeLayout { timestamp_bits, node_bits, sequence_bits })
+    }
+
+    /// Returns the largest timestamp the layout can hold, or zero if it has no timestamp.
+    pub const fn max_timestamp(&self) -> u64 {
+        mask(self.timestamp_bits)
+    }
+
+    /// Returns the largest node number the layout can hold.
+    pub const fn max_node(&self) -> u64 {
+        mask(self.node_bits)
+    }
+
+    /// Returns the largest sequence number the layout can hold.
+    pub const fn max_sequence(&self) -> u64 {
+        mask(self.sequence_bits)
+    }
+
+    /// Packs `parts` into an ID, or returns an error if a part doesn't fit.
+    pub fn encode(&self, parts: SnowflakeParts) -> Result<SnowflakeID, SnowflakeError> {
+        if parts.timestamp > self.max_timestamp() {
+            return Err(SnowflakeError::TimestampOutOfRange);
+        }
+        if parts.node > self.max_node() {
+            return Err(SnowflakeError::NodeOutOfRange);
+        }
+        if parts.sequence > self.max_sequence() {
+            return Err(SnowflakeError::Exhausted);
+        }
+        let timestamp = parts.timestamp << (self.node_bits + self.sequence_bits);
+        Ok(SnowflakeID(timestamp | parts.node << self.sequence_bits | parts.sequence))
+    }
+
+    /// Unpacks an ID created with this layout.
+    pub fn decode(&self, id: SnowflakeID) -> SnowflakeParts {
+        SnowflakeParts {
+            timestamp: id.0 >> (self.node_bits + self.sequence_bits) & self.max_timestamp(),
+            node: id.0 >> self.sequence_bits & self.max_node(),
+            sequence: id.0 & self.max_sequence(),
+        }
+    }
+}
+
+const fn mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        bits => u64::MAX >> (64 - bits),
+    }
+}
+
+/// The parts of a [`SnowflakeID`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SnowflakeParts {
+    /// When the ID was created, in whatever unit the generator was given. Zero for layouts without a timestamp.
+    pub timestamp: u64,
+    /// The node that created the ID.
+    pub node: u64,
+    /// The position of the ID among those its node created with the same timestamp.
+    pub sequence: u64,
+}
+
+/// An ID created by a [`SnowflakeGenerator`].
+///
+/// IDs compare by their packed value, so IDs with later timestamps are greater.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnowflakeID(u64);
+
+impl SnowflakeID {
+    /// Returns the packed value, for sending to other processes.
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Rebuilds an ID from its packed value.
+    #[inline]
+    pub const fn from_u64(value: u64) -> Self {
+        SnowflakeID(value)
+    }
+}
+
+impl fmt::Display for SnowflakeID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Error returned when creating or packing a [`SnowflakeID`] fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SnowflakeError {
+    /// The layout needs more than 63 bits or has no sequence bits.
+    InvalidLayout,
+    /// The node number doesn't fit in the layout's node bits.
+    NodeOutOfRange,
+    /// The timestamp doesn't fit in the layout's timestamp bits.
+    TimestampOutOfRange,
+    /// The node has used up its sequence for the current timestamp. With a timestamp, retrying at a later timestamp
+    /// will succeed.
+    Exhausted,
+}
+
+impl fmt::Display for SnowflakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SnowflakeError::InvalidLayout => "snowflake layout must fit in 63 bits with at least one sequence bit",
+            SnowflakeError::NodeOutOfRange => "node doesn't fit in the snowflake layout",
+            SnowflakeError::TimestampOutOfRange => "timestamp doesn't fit in the snowflake layout",
+            SnowflakeError::Exhausted => "snowflake sequence exhausted",
+        })
+    }
+}
+
+impl core::error::Error for SnowflakeError {}
+
+/// Creates [`SnowflakeID`]s for a single node.
+///
+/// The generator remembers the last timestamp and sequence it used, packed as `timestamp << sequence_bits |
+/// sequence`, so IDs are unique and increasing even if timestamps go backwards.
+pub struct SnowflakeGenerator {
+    layout: SnowflakeLayout,
+    node: u64,
+    /// The last timestamp and sequence used, or [`UNUSED`] before the first ID.
+    last: Atomic64,
+}
+
+/// A `last` value that no layout can produce, since they fit in 63 bits.
+const UNUSED: u64 = u64::MAX;
+
+impl SnowflakeGenerator {
+    /// Creates a generator for `node`, or returns [`SnowflakeError::NodeOutOfRange`] if it doesn't fit the layout.
+    pub fn new(layout: SnowflakeLayout, node: u64) -> Result<Self, SnowflakeError> {
+        if node > layout.max_node() {
+            return Err(SnowflakeError::NodeOutOfRange);
+        }
+        Ok(SnowflakeGenerator { layout, node, last: Atomic64::new(UNUSED) })
+    }
+
+    /// Returns the generator's layout.
+    pub fn layout(&self) -> SnowflakeLayout {
+        self.layout
+    }
+
+    /// Returns the node this generator creates IDs for.
+    pub fn node(&self) -> u64 {
+        self.node
+    }
+
+    /// Creates an ID with a timestamp of zero. This is meant for layouts without timestamp bits, where the whole
+    /// sequence belongs to one timestamp.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&self) -> Result<SnowflakeID, SnowflakeError> {
+        self.next_at(0)
+    }
+
+    /// Creates an ID stamped with `timestamp`.
+    ///
+    /// If `timestamp` is earlier than or equal to the last one used, the last timestamp is reused with the next
+    /// sequence number, so IDs never repeat when the clock steps backwards. Returns [`SnowflakeError::Exhausted`]
+    /// if that sequence is used up.
+    pub fn next_at(&self, timestamp: u64) -> Result<SnowflakeID, SnowflakeError> {
+        if timestamp > self.layout.max_timestamp() {
+            return Err(SnowflakeError::TimestampOutOfRange);
+        }
+
+        let sequence_bits = self.layout.sequence_bits;
+        let max_sequence = self.layout.max_sequence();
+        let mut result = Err(SnowflakeError::Exhausted);
+        let _ = self.last.fetch_update(|last| {
+            // The closure reruns if another thread got in first, so only the final attempt's result counts.
+            result = Err(SnowflakeError::Exhausted);
+            let (timestamp, sequence) = match last {
+                UNUSED => (timestamp, 0),
+                last if timestamp > last >> sequence_bits => (timestamp, 0),
+                last if last & max_sequence < max_sequence => (last >> sequence_bits, (last & max_sequence) + 1),
+                _ => return None,
+            };
+            result = Ok(SnowflakeParts { timestamp, node: self.node, sequence });
+            Some(timestamp << sequence_bits | sequence)
+        });
+        self.layout.encode(result?)
+    }
+
+    /// Creates an ID stamped with the number of milliseconds between `epoch` and now.
+    ///
+    /// Returns [`SnowflakeError::TimestampOutOfRange`] if the clock is before `epoch`.
+    #[cfg(feature = "std")]
+    pub fn next_since(&self, epoch: std::time::SystemTime) -> Result<SnowflakeID, SnowflakeError> {
+        let elapsed = std::time::SystemTime::now().duration_since(epoch);
+        let millis = elapsed.ok().and_then(|elapsed| u64::try_from(elapsed.as_millis()).ok());
+        self.next_at(millis.ok_or(SnowflakeError::TimestampOutOfRange)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::{SnowflakeError, SnowflakeGenerator, SnowflakeID, SnowflakeLayout, SnowflakeParts};
+    use std::collections::HashSet;
+    use std::vec::Vec;
+
+    #[test]
+    fn layout_budget() {
+        assert_eq!(SnowflakeLayout::new(41, 10, 13), Err(SnowflakeError::InvalidLayout));
+        assert_eq!(SnowflakeLayout::new(10, 10, 0), Err(SnowflakeError::InvalidLayout));
+        assert_eq!(SnowflakeLayout::new(u32::MAX, 1, 1), Err(SnowflakeError::InvalidLayout));
+        assert_eq!(SnowflakeLayout::new(1, u32::MAX, 1), Err(SnowflakeError::InvalidLayout));
+        assert_eq!(SnowflakeLayout::new(1, 1, u32::MAX), Err(SnowflakeError::InvalidLayout));
+        assert_eq!(SnowflakeLayout::new(41, 10, 12), Ok(SnowflakeLayout::CLASSIC));
+        assert_eq!(SnowflakeLayout::CLASSIC.max_node(), 1023);
+        assert_eq!(SnowflakeLayout::new(0, 0, 63).unwrap().max_sequence(), i64::MAX as u64);
+        assert_eq!(SnowflakeLayout::new(0, 8, 8).unwrap().max_timestamp(), 0);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let layout = SnowflakeLayout::CLASSIC;
+        let parts = SnowflakeParts { timestamp: layout.max_timestamp(), node: 513, sequence: 4095 };
+        let id = layout.encode(parts).unwrap();
+        assert_eq!(layout.decode(id), parts);
+        assert_eq!(layout.decode(SnowflakeID::from_u64(id.get())), parts);
+        assert!(id.get() <= i64::MAX as u64);
+
+        let invalid = |parts| layout.encode(parts).unwrap_err();
+        assert_eq!(invalid(SnowflakeParts { node: 1024, ..parts }), SnowflakeError::NodeOutOfRange);
+        assert_eq!(invalid(SnowflakeParts { sequence: 4096, ..parts }), SnowflakeError::Exhausted);
+        assert_eq!(
+            invalid(SnowflakeParts { timestamp: 1 << 41, ..parts }),
+            SnowflakeError::TimestampOutOfRange
+        );
+    }
+
+    #[test]
+    fn node_validation() {
+        assert!(SnowflakeGenerator::new(SnowflakeLayout::CLASSIC, 1023).is_ok());
+        assert_eq!(
+            SnowflakeGenerator::new(SnowflakeLayout::CLASSIC, 1024).err(),
+            Some(SnowflakeError::NodeOutOfRange)
+        );
+    }
+
+    #[test]
+    fn sequence_exhaustion() {
+        let layout = SnowflakeLayout::new(4, 2, 2).unwrap();
+        let generator = SnowflakeGenerator::new(layout, 1).unwrap();
+
+        let sequences: Vec<u64> = (0..4).map(|_| layout.decode(generator.next_at(5).unwrap()).sequence).collect();
+        assert_eq!(sequences, [0, 1, 2, 3]);
+        assert_eq!(generator.next_at(5), Err(SnowflakeError::Exhausted));
+        // An older timestamp reuses the last one, which is still exhausted.
+        assert_eq!(generator.next_at(3), Err(SnowflakeError::Exhausted));
+
+        let id = generator.next_at(6).unwrap();
+        assert_eq!(layout.decode(id), SnowflakeParts { timestamp: 6, node: 1, sequence: 0 });
+        assert_eq!(generator.next_at(16), Err(SnowflakeError::TimestampOutOfRange));
+    }
+
+    #[test]
+    fn clock_going_backwards() {
+        let generator = SnowflakeGenerator::new(SnowflakeLayout::CLASSIC, 0).unwrap();
+        let a = generator.next_at(100).unwrap();
+        let b = generator.next_at(50).unwrap();
+        assert!(a < b);
+        assert_eq!(SnowflakeLayout::CLASSIC.decode(b).timestamp, 100);
+    }
+
+    #[test]
+    fn unique_across_nodes_and_threads() {
+        let layout = SnowflakeLayout::new(0, 4, 32).unwrap();
+        let generators: Vec<_> = (0..4).map(|node| SnowflakeGenerator::new(layout, node).unwrap()).collect();
+        let ids: Vec<SnowflakeID> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|thread| {
+                    let generator = &generators[thread % generators.len()];
+                    scope.spawn(move || (0..10000).map(|_| generator.next().unwrap()).collect::<Vec<_>>())
+                })
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wall_clock() {
+        let generator = SnowflakeGenerator::new(SnowflakeLayout::CLASSIC, 7).unwrap();
+        let epoch = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        let parts = SnowflakeLayout::CLASSIC.decode(generator.next_since(epoch).unwrap());
+        assert!(parts.timestamp >= 60_000);
+        assert_eq!(parts.node, 7);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::domain::IdDomain;
+use crate::exhaustion::{exhaustion_policy, Exhausted, LIMIT};
+use crate::TaggedRuntimeID;
+use core::num::NonZeroUsize;
+
+/// A generator of ID values that can be passed to [`RuntimeID::new_in`](TaggedRuntimeID::new_in).
+///
+/// Libraries that create IDs can accept a source instead of calling [`RuntimeID::new`](TaggedRuntimeID::new)
+/// directly, so callers can inject a deterministic sequence in tests. IDs are only guaranteed to be unique among IDs
+/// from the same source; [`GlobalSource`] gives the same IDs as [`RuntimeID::new`](TaggedRuntimeID::new). Sources
+/// that span processes, such as a counter file or a lease server, are unique among every process using the same
+/// file or server, but their IDs can collide with those from [`RuntimeID::new`](TaggedRuntimeID::new).
+///
+/// # Example
+/// ```
+/// use runtime_id::{IdSource, RuntimeID, SequenceSource};
+///
+/// fn spawn_workers(source: &impl IdSource) -> Vec<RuntimeID> {
+///     (0..3).map(|_| RuntimeID::new_in(source)).collect()
+/// }
+///
+/// let source = SequenceSource::new();
+/// let workers = spawn_workers(&source);
+/// assert_eq!(workers[2].to_string(), "3");
+/// ```
+pub trait IdSource {
+    /// Takes the next ID value, or returns [`Exhausted`] if the source has run out.
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted>;
+}
+
+impl<S: IdSource + ?Sized> IdSource for &S {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        (**self).try_next_id()
+    }
+}
+
+/// The global counter behind [`RuntimeID::new`](TaggedRuntimeID::new).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobalSource;
+
+impl IdSource for GlobalSource {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        crate::take(core::sync::atomic::Ordering::Relaxed).map(non_zero)
+    }
+}
+
+/// The per-thread blocks behind [`RuntimeID::new_local`](TaggedRuntimeID::new_local). IDs are unique alongside
+/// those from [`GlobalSource`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalSource;
+
+#[cfg(feature = "std")]
+impl IdSource for LocalSource {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        crate::local::next_local().map(non_zero)
+    }
+}
+
+/// A deterministic sequence counting up from a chosen first value, independent of every other source.
+pub struct SequenceSource {
+    domain: IdDomain,
+}
+
+impl SequenceSource {
+    /// Creates a sequence starting at one.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self::starting_at(NonZeroUsize::MIN)
+    }
+
+    /// Creates a sequence whose first ID is `first`.
+    pub const fn starting_at(first: NonZeroUsize) -> Self {
+        SequenceSource { domain: IdDomain::starting_at(first.get()) }
+    }
+}
+
+impl IdSource for SequenceSource {
+    #[inline]
+    fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+        self.domain.try_next_id().map(non_zero)
+    }
+}
+
+#[inline]
+pub(crate) fn non_zero(id: usize) -> NonZeroUsize {
+    match NonZeroUsize::new(id) {
+        Some(id) => id,
+        None => unreachable!("sources start at one and never wrap"),
+    }
+}
+
+impl<Tag> TaggedRuntimeID<Tag> {
+    /// Creates a new ID from `source`.
+    ///
+    /// If the source is exhausted the current [`ExhaustionPolicy`](crate::ExhaustionPolicy) decides what happens.
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn new_in(source: &impl IdSource) -> Self {
+        match source.try_next_id() {
+            Ok(id) => Self::from_raw(id).track(),
+            Err(Exhausted) => Self::from_counter(exhaustion_policy().handle(LIMIT)).track(),
+        }
+    }
+
+    /// Creates a new ID from `source`, or returns [`Exhausted`] if the source has run out.
+    #[inline]
+    #[cfg_attr(all(feature = "track-origin", debug_assertions), track_caller)]
+    pub fn try_new_in(source: &impl IdSource) -> Result<Self, Exhausted> {
+        Ok(Self::from_raw(source.try_next_id()?).track())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Exhausted, GlobalSource, IdSource, RuntimeID, SequenceSource};
+    use core::cell::Cell;
+    use core::num::NonZeroUsize;
+
+    #[test]
+    fn sequence() {
+        let source = SequenceSource::starting_at(NonZeroUsize::new(100).unwrap());
+        let a = RuntimeID::new_in(&source);
+        let b = RuntimeID::new_in(&source);
+        assert_eq!((a.id.get(), b.id.get()), (100, 101));
+
+        let again = SequenceSource::starting_at(NonZeroUsize::new(100).unwrap());
+        assert_eq!(RuntimeID::new_in(&again), a);
+    }
+
+    #[test]
+    fn global() {
+        let a = RuntimeID::new_in(&GlobalSource);
+        let b = RuntimeID::new();
+        assert!(a < b);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn local() {
+        let a = RuntimeID::new_in(&crate::LocalSource);
+        assert_ne!(a, RuntimeID::new());
+    }
+
+    #[test]
+    fn custom() {
+        struct Fixed(Cell<usize>);
+        impl IdSource for Fixed {
+            fn try_next_id(&self) -> Result<NonZeroUsize, Exhausted> {
+                let left = self.0.get().checked_sub(1).ok_or(Exhausted)?;
+                self.0.set(left);
+                Ok(NonZeroUsize::new(42).unwrap())
+            }
+        }
+
+        let source = Fixed(Cell::new(1));
+        assert_eq!(RuntimeID::try_new_in(&source).unwrap().id.get(), 42);
+        assert_eq!(RuntimeID::try_new_in(&&source), Err(Exhausted));
+    }
+}
@@ -0,0 +1,148 @@
+//! Deterministic IDs for tests.
+//!
+//! Tests that embed IDs in snapshots break when the order tests run in changes, because every test in the binary
+//! shares the global counter. Inside [`scoped`], IDs created on the current thread come from a fresh sequence
+//! instead, leaving the global counter untouched.
+//!
+//! # Example
+//! ```
+//! use runtime_id::{testing, RuntimeID};
+//!
+//! let ids = testing::scoped(|| [RuntimeID::new(), RuntimeID::new()]);
+//! assert_eq!(ids.map(|id| id.to_string()), ["1", "2"]);
+//! ```
+
+use crate::exhaustion::{Exhausted, LIMIT};
+use std::cell::Cell;
+
+std::thread_local! {
+    /// The current scope's sequence as `(first, next)`, or zeros outside of a scope.
+    static SEQUENCE: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Runs `f` with IDs created on the current thread counting up from one.
+///
+/// This covers [`RuntimeID::new`](crate::TaggedRuntimeID::new) and the other constructors that use the global
+/// counter, as well as [`GlobalSource`](crate::GlobalSource) and [`LocalSource`](crate::LocalSource). Other threads,
+/// including ones spawned inside `f`, keep using the global counter. Scopes can be nested; the outer sequence
+/// resumes where it left off when the inner scope ends.
+pub fn scoped<R>(f: impl FnOnce() -> R) -> R {
+    scoped_from(1, f)
+}
+
+/// Like [`scoped`], but the first ID is `first`.
+///
+/// # Panics
+/// Panics if `first` is zero, or too large to be an ID.
+pub fn scoped_from<R>(first: usize, f: impl FnOnce() -> R) -> R {
+    assert!((1..=LIMIT).contains(&first), "scoped IDs must start between 1 and isize::MAX");
+
+    struct Restore((usize, usize));
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SEQUENCE.with(|sequence| sequence.set(self.0));
+        }
+    }
+
+    let _restore = Restore(SEQUENCE.with(|sequence| sequence.replace((first, first))));
+    f()
+}
+
+/// Takes `count` consecutive IDs from the current thread's scope, or returns `None` outside of a scope.
+#[inline]
+pub(crate) fn try_take(count: usize) -> Option<Result<usize, Exhausted>> {
+    SEQUENCE
+        .try_with(|sequence| {
+            let (first, next) = sequence.get();
+            if first == 0 {
+                return None;
+            }
+            Some(match next.checked_add(count).filter(|&end| end <= LIMIT + 1) {
+                Some(end) => {
+                    sequence.set((first, end));
+                    Ok(next)
+                }
+                None => Err(Exhausted),
+            })
+        })
+        .ok()
+        .flatten()
+}
+
+/// Returns whether `id` has been created in the current thread's scope, or `None` outside of a scope.
+pub(crate) fn has_issued(id: usize) -> Option<bool> {
+    let (first, next) = SEQUENCE.try_with(Cell::get).ok()?;
+    (first != 0).then(|| (first..next).contains(&id))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{scoped, scoped_from};
+    use crate::{GlobalSource, LocalSource, RuntimeID};
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    fn raw(id: RuntimeID) -> usize {
+        id.id.get()
+    }
+
+    #[test]
+    fn deterministic() {
+        let run = || {
+            scoped(|| {
+                let mut ids = std::vec![raw(RuntimeID::new()), raw(RuntimeID::new_ordered())];
+                ids.extend(RuntimeID::reserve(3).map(raw));
+                ids.push(raw(RuntimeID::new_local()));
+                ids.push(raw(RuntimeID::new_in(&GlobalSource)));
+                ids.push(raw(RuntimeID::new_in(&LocalSource)));
+                ids.push(raw(RuntimeID::try_new().unwrap()));
+                ids
+            })
+        };
+        assert_eq!(run(), (1..=9).collect::<Vec<_>>());
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn starting_value() {
+        scoped_from(1000, || {
+            assert_eq!(RuntimeID::new().to_string(), "1000");
+            assert_eq!("1000".parse::<RuntimeID>().map(raw), Ok(1000));
+            assert!("1001".parse::<RuntimeID>().is_err());
+        });
+    }
+
+    #[test]
+    fn nested() {
+        scoped(|| {
+            assert_eq!(raw(RuntimeID::new()), 1);
+            scoped_from(50, || assert_eq!(raw(RuntimeID::new()), 50));
+            assert_eq!(raw(RuntimeID::new()), 2);
+        });
+    }
+
+    #[test]
+    fn restored_after_panic() {
+        let result = std::panic::catch_unwind(|| {
+            scoped(|| {
+                RuntimeID::new();
+                panic!("test panic");
+            })
+        });
+        assert!(result.is_err());
+        assert!(super::has_issued(1).is_none());
+    }
+
+    #[test]
+    fn global_counter_untouched() {
+        let before = raw(RuntimeID::new());
+        scoped(|| {
+            RuntimeID::reserve(1 << 40);
+            assert_eq!(raw(RuntimeID::new()), (1 << 40) + 1);
+            let other_thread = std::thread::spawn(|| raw(RuntimeID::new())).join().unwrap();
+            assert!(other_thread > before && other_thread < 1 << 40);
+        });
+        let after = raw(RuntimeID::new());
+        assert!(after > before && after < 1 << 40);
+    }
+}